@@ -0,0 +1,8 @@
+// `everything.rs` is the output of `to_module("everything")` for a pattern calling every
+// builder function, so the generated code is checked by the compiler
+#[path = "generated_module/everything.rs"]
+mod everything;
+
+fn main() {
+    println!("{}", everything::everything().to_string_fancy());
+}
@@ -0,0 +1,5 @@
+use ezregexp::*;
+
+pub fn everything() -> Pattern {
+    at_start().and_then(anything()).and_then(digit()).times(2).and_then(letter()).at_least(2).and_then(number()).at_most(3).and_then(word_character()).many(2,4).lazy().and_maybe(whitespace()).and_then(tab()).and_many(newline()).and_maybe_many(carriage_return()).and_then(control_char('A')).and_then(hex_char(0xFEFF)).and_then(word_boundary()).and_then(not_word_boundary()).and_then(posix_class("alpha")).and_then(unicode_category("Greek")).and_then(any_except(unicode_category("Greek"))).and_then(any_except(digit())).and_then(char_range('a', 'z')).and_then(one_of(['x', 'y'])).and_then(any_except(char_class().range('a', 'c').chars(['_']))).and_either(("cat", "dog")).and_then(raw("\\d")).and_then(group("ab")).and_then(text("cd").captured()).and_then(letter().named("l")).and_then(text("e").case_insensitive().without_flags("s")).and_then(text("f").multiline().dotall().flags("U")).and_then(followed_by("g")).and_then(not_followed_by("h")).and_then(preceded_by("i")).and_then(not_preceded_by("j")).and_then(backref(1)).and_then(backref_name("l")).and_then(text_ci("k")).and_then(anything_including_newline()).and_then(uuid()).and_then(at_text_start()).and_maybe("m").and_maybe_many("n").and_many("o").must_end()
+}
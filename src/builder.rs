@@ -1,6 +1,8 @@
 //! Build regular expression from fluent API
 
 use itertools::Itertools;
+use regex_syntax::ast::{parse::Parser, Ast, ErrorKind};
+use std::cell::Cell;
 use std::fmt::{Display, Formatter, Result};
 
 /// Generate Rust code
@@ -9,7 +11,8 @@ pub trait ToCode {
 }
 
 /// A Regular Expression Pattern
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Pattern {
     /// Sequence of patterns
     Sequence(Vec<Pattern>),
@@ -24,72 +27,401 @@ pub enum Pattern {
         exp: Box<Pattern>,
         low: u32,
         high: u32,
+        lazy: bool,
     },
     /// Digit
     Digit,
-    /// Letter,
+    /// Letter
     Letter,
+    /// Unicode number, including non-ASCII digits
+    Number,
     /// Word characters
     WordCharacter,
+    /// Whitespace
+    Whitespace,
     /// Start of line/input
     InputStart,
     /// End of line/input
     InputEnd,
+    /// Start of text, even in multi-line mode
+    TextStart,
+    /// End of text, even in multi-line mode
+    TextEnd,
+    /// Word boundary, negated or not
+    WordBoundary { negated: bool },
     /// Negation
     Not(Box<Pattern>),
     /// Anything
     Any,
     /// Named group
     Named{exp:Box<Pattern>,name:String},
+    /// Control character
+    Control(char),
+    /// Character given by its code point
+    HexChar(u32),
+    /// Curated helper: renders as its expansion, generates code as the helper call
+    Curated { exp: Box<Pattern>, code: String },
+    /// Group, capturing or not
+    Group { exp: Box<Pattern>, capturing: bool },
+    /// POSIX character class, like `alpha`
+    PosixClass(String),
+    /// Lookahead assertion, negated or not
+    LookAhead { exp: Box<Pattern>, negated: bool },
+    /// Lookbehind assertion, negated or not
+    LookBehind { exp: Box<Pattern>, negated: bool },
+    /// Pattern with inline flags, like `i` for case insensitivity
+    Flags { flags: String, exp: Box<Pattern> },
+    /// Range of characters, inclusive
+    CharRange { from: char, to: char },
+    /// Set of characters and ranges, negated or not
+    CharSet { items: Vec<ClassItem>, negated: bool },
+    /// Backreference to a group captured earlier
+    BackRef(BackRefKind),
+    /// Unicode class, like the `Lu` general category or the `Greek` script, negated or not
+    UnicodeClass { name: String, negated: bool },
 }
 
-/// Generate regular expression from Pattern
+/// Group referenced by a backreference
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BackRefKind {
+    /// Group name: `\k<name>`
+    Name(String),
+    /// Group index, starting at 1: `\1`
+    Index(u32),
+}
+
+/// Item of a character set
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClassItem {
+    /// A single character
+    Char(char),
+    /// A range of characters, inclusive
+    Range(char, char),
+}
+
+impl ClassItem {
+    /// Content of a character class for the item
+    fn class_text(&self) -> String {
+        match self {
+            ClassItem::Char(c) => class_escape(*c),
+            ClassItem::Range(from, to) => format!("{}-{}", class_escape(*from), class_escape(*to)),
+        }
+    }
+}
+
+/// Generate regular expression from Pattern. Kept for convenience when debugging,
+/// use `to_regex_string` to get the final regular expression
 impl Display for Pattern {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        match self {
-            Pattern::Sequence(v) => v
-                .iter()
-                .map(|e| match e {
-                    Pattern::Or(..) if v.len() > 1 => write!(f, "({})", e),
-                    _ => write!(f, "{}", e),
+        self.render(RenderOptions::current()).fmt(f)
+    }
+}
+
+/// Rendering configuration, for the current thread.
+/// `ascii` renders `digit()`, `number()`, `letter()` and `word_character()` as ASCII classes
+/// instead of Unicode shorthands. The `ascii_digit()`, `ascii_letter()` and
+/// `ascii_word_character()` helpers are always ASCII, whatever the configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BuilderConfig {
+    /// Render character classes as ASCII
+    pub ascii: bool,
+}
+
+thread_local! {
+    static CONFIG: Cell<BuilderConfig> = Cell::new(BuilderConfig::default());
+}
+
+impl BuilderConfig {
+    /// Configuration of the current thread
+    pub fn current() -> Self {
+        CONFIG.with(|c| c.get())
+    }
+
+    /// Use this configuration for the current thread
+    pub fn set(self) {
+        CONFIG.with(|c| c.set(self))
+    }
+}
+
+/// ASCII digit class
+pub(crate) const ASCII_DIGIT: &str = "[0-9]";
+/// ASCII letter class
+pub(crate) const ASCII_LETTER: &str = "[a-zA-Z]";
+/// ASCII word character class
+pub(crate) const ASCII_WORD_CHARACTER: &str = "[0-9A-Za-z_]";
+/// ASCII whitespace class
+pub(crate) const ASCII_WHITESPACE: &str = r"[\t\n\x0B\x0C\r ]";
+
+/// Options for rendering a pattern into a regular expression
+#[derive(Clone, Copy, Default)]
+struct RenderOptions {
+    /// are all groups non-capturing?
+    uncaptured: bool,
+    /// are character classes ASCII?
+    ascii: bool,
+    /// are forward slashes escaped?
+    escape_slashes: bool,
+    /// is the regex in verbose mode, where whitespace and `#` need escaping?
+    verbose: bool,
+}
+
+/// RenderOptions helper function
+impl RenderOptions {
+    /// Options from the thread configuration
+    fn current() -> Self {
+        RenderOptions {
+            ascii: BuilderConfig::current().ascii,
+            ..Default::default()
+        }
+    }
+
+    /// Opening of a group that captures unless disabled
+    fn open(&self) -> &'static str {
+        if self.uncaptured {
+            "(?:"
+        } else {
+            "("
+        }
+    }
+
+    /// Literal text, with forward slashes escaped if needed
+    fn literal<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.escape_slashes && text.contains('/') {
+            text.replace('/', r"\/").into()
+        } else {
+            text.into()
+        }
+    }
+
+    /// Text, with whitespace and comment start escaped in verbose mode
+    fn verbose_text<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.verbose && text.contains(|c: char| c.is_whitespace() || c == '#') {
+            text.chars()
+                .map(|c| match c {
+                    ' ' | '#' => format!("\\{}", c),
+                    c if c.is_whitespace() => format!(r"\x{{{:X}}}", c as u32),
+                    c => c.to_string(),
+                })
+                .collect::<String>()
+                .into()
+        } else {
+            text.into()
+        }
+    }
+
+    /// Class items, with whitespace escaped in verbose mode. `#` is already escaped in classes
+    fn verbose_class<'a>(&self, items: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.verbose && items.contains(char::is_whitespace) {
+            items
+                .chars()
+                .map(|c| match c {
+                    ' ' => r"\ ".to_string(),
+                    c if c.is_whitespace() => format!(r"\x{{{:X}}}", c as u32),
+                    c => c.to_string(),
                 })
-                .collect(),
-            Pattern::Text(t) => write!(f, "{}", t),
-            Pattern::Raw(t) => write!(f, "{}", t),
-            Pattern::Or(v) => v
-                .iter()
-                .intersperse(&Pattern::Raw("|".to_owned()))
-                .map(|e| write!(f, "{}", e))
-                .collect::<Result>(),
-            Pattern::Many { exp, low, high } => {
-                let mut s = format!("{}", exp);
-                if s.len() > 2 || (s.len() == 2 && s.chars().into_iter().next().unwrap() != '\\') {
-                    s = format!("({})", s);
+                .collect::<String>()
+                .into()
+        } else {
+            items.into()
+        }
+    }
+
+    /// Class items, escaped for verbose mode and forward slashes
+    fn class(&self, items: &str) -> String {
+        self.literal(&self.verbose_class(items)).into_owned()
+    }
+}
+
+/// A pattern rendered with given options
+struct Render<'a> {
+    pattern: &'a Pattern,
+    options: RenderOptions,
+}
+
+/// Generate regular expression from Pattern, honoring the options
+impl Display for Render<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let o = self.options;
+        match self.pattern {
+            Pattern::Sequence(v) => v.iter().try_for_each(|e| match e {
+                Pattern::Or(..) if v.len() > 1 && !e.is_class_union() => {
+                    write!(f, "{}{})", o.open(), e.render(o))
+                }
+                _ => write!(f, "{}", e.render(o)),
+            }),
+            Pattern::Text(t) => write!(f, "{}", o.literal(&o.verbose_text(&escape_text(t)))),
+            Pattern::Raw(t) => write!(f, "{}", o.literal(t)),
+            Pattern::Or(..) if self.pattern.is_class_union() => {
+                let items = self.pattern.class_items().unwrap_or_default();
+                write!(f, "[{}]", o.class(&items))
+            }
+            Pattern::Or(v) => Itertools::intersperse(v.iter(), &Pattern::Raw("|".to_owned()))
+                .try_for_each(|e| write!(f, "{}", e.render(o))),
+            Pattern::Many {
+                exp,
+                low,
+                high,
+                lazy,
+            } => {
+                // anchors are zero-width, repeating them is useless
+                if exp.is_anchor() {
+                    return if *low > 0 {
+                        write!(f, "{}", exp.render(o))
+                    } else {
+                        Ok(())
+                    };
                 }
+                let s = match exp.quantified_group() {
+                    QuantifiedGroup::Bare => exp.render(o).to_string(),
+                    QuantifiedGroup::NonCapturing => format!("(?:{})", exp.render(o)),
+                    QuantifiedGroup::Capturing => format!("{}{})", o.open(), exp.render(o)),
+                };
                 match (low, high) {
                     (0, 1) => write!(f, "{}?", s),
                     (0, 0) => write!(f, "{}*", s),
                     (1, 0) => write!(f, "{}+", s),
                     (l, h) if l == h => write!(f, "{}{{{}}}", s, l),
+                    (l, 0) => write!(f, "{}{{{},}}", s, l),
                     (l, h) => write!(f, "{}{{{},{}}}", s, l, h),
+                }?;
+                if *lazy {
+                    write!(f, "?")?;
                 }
+                Ok(())
             }
+            Pattern::Digit if o.ascii => write!(f, "{}", ASCII_DIGIT),
+            Pattern::Letter if o.ascii => write!(f, "{}", ASCII_LETTER),
+            Pattern::Number if o.ascii => write!(f, "{}", ASCII_DIGIT),
+            Pattern::WordCharacter if o.ascii => write!(f, "{}", ASCII_WORD_CHARACTER),
+            Pattern::Whitespace if o.ascii => write!(f, "{}", ASCII_WHITESPACE),
             Pattern::Digit => write!(f, r"\d"),
-            Pattern::Letter => write!(f, r"\pN"),
+            Pattern::Letter => write!(f, r"\pL"),
+            Pattern::Number => write!(f, r"\pN"),
             Pattern::WordCharacter => write!(f, r"\w"),
+            Pattern::Whitespace => write!(f, r"\s"),
             Pattern::InputStart => write!(f, "^"),
             Pattern::InputEnd => write!(f, "$"),
+            Pattern::TextStart => write!(f, r"\A"),
+            Pattern::TextEnd => write!(f, r"\z"),
+            Pattern::WordBoundary { negated: false } => write!(f, r"\b"),
+            Pattern::WordBoundary { negated: true } => write!(f, r"\B"),
+            Pattern::Not(exp) if exp.class_items().is_some() => {
+                let items = exp.class_items().unwrap_or_default();
+                write!(f, "[^{}]", o.class(&items))
+            }
+            Pattern::Not(exp) if o.ascii => match **exp {
+                Pattern::Digit => write!(f, "[^{}", &ASCII_DIGIT[1..]),
+                Pattern::Letter => write!(f, "[^{}", &ASCII_LETTER[1..]),
+                Pattern::Number => write!(f, "[^{}", &ASCII_DIGIT[1..]),
+                Pattern::WordCharacter => write!(f, "[^{}", &ASCII_WORD_CHARACTER[1..]),
+                Pattern::Whitespace => write!(f, "[^{}", &ASCII_WHITESPACE[1..]),
+                Pattern::PosixClass(ref name) => write!(f, "[[:^{}:]]", name),
+                _ => write!(f, ""),
+            },
             Pattern::Not(exp)=> {
                 match **exp {
                     Pattern::Digit => write!(f, r"\D"),
-                    Pattern::Letter => write!(f, r"\PN"),
+                    Pattern::Letter => write!(f, r"\PL"),
+                    Pattern::Number => write!(f, r"\PN"),
                     Pattern::WordCharacter => write!(f, r"\W"),
+                    Pattern::Whitespace => write!(f, r"\S"),
+                    Pattern::PosixClass(ref name) => write!(f, "[[:^{}:]]", name),
                     _ => write!(f, ""),
                 }
             }
             Pattern::Any => write!(f,"."),
-            Pattern::Named{exp, name} => write!(f, r"(?P<{}>{})",name,exp),
-            
+            Pattern::Named { exp, .. } if o.uncaptured => write!(f, "(?:{})", exp.render(o)),
+            Pattern::Named{exp, name} => write!(f, r"(?P<{}>{})",name,exp.render(o)),
+            Pattern::Control(c) => write!(f, "{}", control_escape(*c)),
+            Pattern::HexChar(code) => write!(f, "{}", hex_escape(*code)),
+            Pattern::BackRef(BackRefKind::Name(name)) => write!(f, r"\k<{}>", name),
+            Pattern::BackRef(BackRefKind::Index(index)) => write!(f, r"\{}", index),
+            Pattern::Curated { exp, .. } => write!(f, "{}", exp.render(o)),
+            Pattern::Group {
+                exp,
+                capturing: true,
+            } if !o.uncaptured => write!(f, "({})", exp.render(o)),
+            Pattern::Group { exp, .. } => write!(f, "(?:{})", exp.render(o)),
+            Pattern::PosixClass(name) => write!(f, "[[:{}:]]", name),
+            Pattern::UnicodeClass {
+                name,
+                negated: false,
+            } => write!(f, r"\p{{{}}}", name),
+            Pattern::UnicodeClass {
+                name,
+                negated: true,
+            } => write!(f, r"\P{{{}}}", name),
+            Pattern::LookAhead { exp, negated } => {
+                write!(f, "(?{}{})", if *negated { '!' } else { '=' }, exp.render(o))
+            }
+            Pattern::LookBehind { exp, negated } => {
+                write!(f, "(?<{}{})", if *negated { '!' } else { '=' }, exp.render(o))
+            }
+            Pattern::Flags { flags, exp } => {
+                // the `x` flag makes whitespace and `#` in the body significant
+                let (on, off) = flags.split_at(flags.find('-').unwrap_or(flags.len()));
+                let verbose = (o.verbose || on.contains('x')) && !off.contains('x');
+                let o = RenderOptions { verbose, ..o };
+                write!(f, "(?{}:{})", flags, exp.render(o))
+            }
+            Pattern::CharSet { items, negated } => {
+                let items: String = items.iter().map(ClassItem::class_text).collect();
+                write!(f, "[{}{}]", if *negated { "^" } else { "" }, o.class(&items))
+            }
+            Pattern::CharRange { from, to } => {
+                let range = format!("{}-{}", class_escape(*from), class_escape(*to));
+                write!(f, "[{}]", o.class(&range))
+            }
+        }
+    }
+}
+
+/// Grouping of a pattern before a quantifier
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum QuantifiedGroup {
+    /// No group, the pattern is a single atom
+    Bare,
+    /// Non capturing group: `(?:exp)`
+    NonCapturing,
+    /// Capturing group, unless rendering uncaptured: `(exp)`
+    Capturing,
+}
+
+/// Repetition bounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    /// Any number of times: `*`
+    ZeroOrMore,
+    /// At least once: `+`
+    OneOrMore,
+    /// Zero or one time: `?`
+    Optional,
+    /// Exactly n times: `{n}`. n must not be 0
+    Exactly(u32),
+    /// Between low and high times, inclusive: `{low,high}`. High must not be 0 or less than low
+    Between(u32, u32),
+    /// At least n times: `{n,}`
+    AtLeast(u32),
+}
+
+impl Repeat {
+    /// Low and high bounds, a high bound of 0 meaning no upper limit.
+    /// Panics if the bounds allow no repetition or are inverted
+    fn bounds(self) -> (u32, u32) {
+        match self {
+            Repeat::ZeroOrMore => (0, 0),
+            Repeat::OneOrMore => (1, 0),
+            Repeat::Optional => (0, 1),
+            Repeat::Exactly(0) => panic!("repetition needs a bound of at least 1"),
+            Repeat::Exactly(n) => (n, n),
+            Repeat::Between(_, 0) => panic!("repetition needs an upper bound of at least 1"),
+            Repeat::Between(low, high) if low > high => {
+                panic!("invalid repetition bounds: {} is greater than {}", low, high)
+            }
+            Repeat::Between(low, high) => (low, high),
+            Repeat::AtLeast(n) => (n, 0),
         }
     }
 }
@@ -153,38 +485,171 @@ impl CodeState {
 impl Pattern {
     /// Conversion to rust code
     fn to_inner_code(&self, state: CodeState) -> String {
+        if let Pattern::Many { lazy: true, .. } = self {
+            let greedy = self.clone().trailing_lazy(false);
+            return format!("{}.lazy()", greedy.to_inner_code(state));
+        }
         if state.first {
             match self {
                 Pattern::Text(txt) => {
                     if state.root {
-                        format!("text(\"{}\")", txt)
+                        format!("text({:?})", txt)
                     } else {
-                        format!("\"{}\"", txt)
+                        format!("{:?}", txt)
                     }
                 }
                 Pattern::Digit => "digit()".to_string(),
-                Pattern::Any => "any()".to_string(),
+                Pattern::Any => "anything()".to_string(),
                 Pattern::Letter => "letter()".to_string(),
+                Pattern::Number => "number()".to_string(),
                 Pattern::WordCharacter => "word_character()".to_string(),
-                Pattern::Or(exps) => format!(
-                    "either(({}))",
-                    exps.iter()
-                        .map(|e| e.to_inner_code(CodeState::first()))
-                        .join(", ")
+                Pattern::Whitespace => "whitespace()".to_string(),
+                Pattern::Control('\t') => "tab()".to_string(),
+                Pattern::Control('\n') => "newline()".to_string(),
+                Pattern::Control('\r') => "carriage_return()".to_string(),
+                Pattern::Control(c) => format!(
+                    "control_char('{}')",
+                    ((*c as u8) | 0x40) as char
+                ),
+                Pattern::HexChar(code) => format!("hex_char(0x{:02X})", code),
+                Pattern::BackRef(BackRefKind::Name(name)) => format!("backref_name({:?})", name),
+                Pattern::BackRef(BackRefKind::Index(index)) => format!("backref({})", index),
+                Pattern::Curated { code, .. } => code.clone(),
+                Pattern::WordBoundary { negated: false } => "word_boundary()".to_string(),
+                Pattern::WordBoundary { negated: true } => "not_word_boundary()".to_string(),
+                Pattern::PosixClass(name) => format!("posix_class({:?})", name),
+                Pattern::UnicodeClass {
+                    name,
+                    negated: false,
+                } => format!("unicode_category({:?})", name),
+                Pattern::UnicodeClass {
+                    name,
+                    negated: true,
+                } => format!("any_except(unicode_category({:?}))", name),
+                Pattern::CharRange { from, to } => format!("char_range({:?}, {:?})", from, to),
+                Pattern::CharSet { items, negated } => {
+                    let code = if items.iter().all(|i| matches!(i, ClassItem::Char(..))) {
+                        format!("one_of([{}])", chars_code(items))
+                    } else {
+                        let mut code = "char_class()".to_string();
+                        for (is_range, group) in
+                            &items.iter().group_by(|i| matches!(i, ClassItem::Range(..)))
+                        {
+                            if is_range {
+                                for item in group {
+                                    if let ClassItem::Range(from, to) = item {
+                                        code.push_str(&format!(".range({:?}, {:?})", from, to));
+                                    }
+                                }
+                            } else {
+                                let group: Vec<ClassItem> = group.copied().collect();
+                                code.push_str(&format!(".chars([{}])", chars_code(&group)));
+                            }
+                        }
+                        code
+                    };
+                    if *negated {
+                        format!("any_except({})", code)
+                    } else {
+                        code
+                    }
+                }
+                Pattern::LookAhead { exp, negated } => format!(
+                    "{}followed_by({})",
+                    if *negated { "not_" } else { "" },
+                    exp.to_inner_code(CodeState::first())
                 ),
+                Pattern::LookBehind { exp, negated } => format!(
+                    "{}preceded_by({})",
+                    if *negated { "not_" } else { "" },
+                    exp.to_inner_code(CodeState::first())
+                ),
+                Pattern::Raw(t) if !t.is_empty() => format!("raw({:?})", t),
+                Pattern::Or(exps) => {
+                    // tuples implement PatternList up to 6 elements
+                    let mut code = format!(
+                        "either(({}))",
+                        exps.iter()
+                            .take(6)
+                            .map(|e| e.to_inner_code(CodeState::first()))
+                            .join(", ")
+                    );
+                    for e in exps.iter().skip(6) {
+                        code.push_str(&format!(".or_else({})", e.to_inner_code(CodeState::first())));
+                    }
+                    code
+                }
                 Pattern::Not (exp ) => format!("any_except({})",exp.to_inner_code(CodeState::first())),
-                Pattern::Many { exp, low, high } if low==high => format!(
+                Pattern::Many {
+                    exp,
+                    low: 0,
+                    high: 1,
+                    ..
+                } => format!("maybe({})", exp.to_inner_code(CodeState::first())),
+                Pattern::Many { exp, low, high, .. } if low==high && *high != 0 => format!(
                     "{}.times({})",
-                    exp.to_inner_code(CodeState::first()),
+                    exp.to_inner_code(CodeState::root()),
+                    low
+                ),
+                Pattern::Many {
+                    exp,
+                    low: 1,
+                    high: 0,
+                    ..
+                } => format!("{}.one_or_more()", exp.to_inner_code(CodeState::root())),
+                Pattern::Many {
+                    exp,
+                    low: 0,
+                    high: 0,
+                    ..
+                } => format!("{}.zero_or_more()", exp.to_inner_code(CodeState::root())),
+                Pattern::Many {
+                    exp, low, high: 0, ..
+                } if *low > 1 => format!(
+                    "{}.at_least({})",
+                    exp.to_inner_code(CodeState::root()),
                     low
                 ),
-                Pattern::Many { exp, low, high } => format!(
+                Pattern::Many {
+                    exp, low: 0, high, ..
+                } if *high > 1 => format!(
+                    "{}.at_most({})",
+                    exp.to_inner_code(CodeState::root()),
+                    high
+                ),
+                Pattern::Many { exp, low, high, .. } => format!(
                     "{}.many({}, {})",
-                    exp.to_inner_code(CodeState::first()),
+                    exp.to_inner_code(CodeState::root()),
                     low,
                     high
                 ),
-                Pattern::Named{exp,name}=>format!(r#"{}.named("{}")"#,exp.to_inner_code(CodeState::first()),name),
+                Pattern::Named{exp,name}=>format!(r#"{}.named("{}")"#,exp.to_inner_code(CodeState::root()),name),
+                Pattern::Group {
+                    exp,
+                    capturing: true,
+                } => format!("{}.captured()", exp.to_inner_code(CodeState::root())),
+                Pattern::Group {
+                    exp,
+                    capturing: false,
+                } => format!("group({})", exp.to_inner_code(CodeState::first())),
+                Pattern::Flags { flags, exp } => {
+                    let (on, off) = flags.split_at(flags.find('-').unwrap_or(flags.len()));
+                    let code = on.chars().fold(
+                        exp.to_inner_code(CodeState::root()),
+                        |code, flag| match flag {
+                            'i' => format!("{}.case_insensitive()", code),
+                            'm' => format!("{}.multiline()", code),
+                            's' => format!("{}.dotall()", code),
+                            _ => format!("{}.flags(\"{}\")", code, flag),
+                        },
+                    );
+                    match off.get(1..) {
+                        Some(off) if !off.is_empty() => {
+                            format!("{}.without_flags(\"{}\")", code, off)
+                        }
+                        _ => code,
+                    }
+                }
                 Pattern::Sequence(exps) => {
                     let mut s = String::new();
                     for e in exps {
@@ -193,6 +658,15 @@ impl Pattern {
                                 Pattern::InputStart => {
                                     s.push_str(&e.to_inner_code(CodeState::first()))
                                 },
+                                Pattern::TextStart => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::WordBoundary { .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::Group {
+                                    capturing: false, ..
+                                } => s.push_str(&e.to_inner_code(CodeState::first())),
                                 Pattern::Not(..) => {
                                     s.push_str(&e.to_inner_code(CodeState::first()))
                                 },
@@ -202,9 +676,51 @@ impl Pattern {
                                 Pattern::Letter => {
                                     s.push_str(&e.to_inner_code(CodeState::first()))
                                 },
+                                Pattern::Number => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
                                 Pattern::WordCharacter => {
                                     s.push_str(&e.to_inner_code(CodeState::first()))
                                 },
+                                Pattern::Whitespace => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::Control(..) => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::HexChar(..) => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::BackRef(..) => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::Curated { .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::Raw(..) => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::PosixClass(..) => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::UnicodeClass { .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::CharRange { .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::CharSet { .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::LookAhead { .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::LookBehind { .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
+                                Pattern::Many { low: 0, high: 1, .. } => {
+                                    s.push_str(&e.to_inner_code(CodeState::first()))
+                                },
                                 _ => s.push_str(&format!(
                                     "start_with({})",
                                     e.to_inner_code(CodeState::first())
@@ -217,20 +733,34 @@ impl Pattern {
                     s
                 }
                 Pattern::InputStart => "at_start()".to_string(),
+                Pattern::TextStart => "at_text_start()".to_string(),
                 _ => String::new(),
             }
         } else {
             match self {
+                Pattern::Or(exps) if exps.len() > 6 => {
+                    format!(".and_then({})", self.to_inner_code(CodeState::first()))
+                }
                 Pattern::Or(exps) => format!(
                     ".and_either(({}))",
                     exps.iter()
                         .map(|e| e.to_inner_code(CodeState::first()))
                         .join(", ")
                 ),
-                Pattern::Many { exp, low, high } => match (low, high) {
+                Pattern::Many { exp, low, high, .. } => match (low, high) {
                     (0, 1) => format!(".and_maybe({})", exp.to_inner_code(CodeState::first())),
                     (0, 0) => format!(".and_maybe_many({})", exp.to_inner_code(CodeState::first())),
                     (1, 0) => format!(".and_many({})", exp.to_inner_code(CodeState::first())),
+                    (l, 0) => format!(
+                        ".and_then({}).at_least({})",
+                        exp.to_inner_code(CodeState::first()),
+                        l
+                    ),
+                    (0, h) => format!(
+                        ".and_then({}).at_most({})",
+                        exp.to_inner_code(CodeState::first()),
+                        h
+                    ),
                     (l, h) if l == h => format!(
                         ".and_then({}).times({})",
                         exp.to_inner_code(CodeState::first()),
@@ -244,17 +774,252 @@ impl Pattern {
                     ),
                 },
                 Pattern::InputEnd => ".must_end()".to_string(),
-                Pattern::Named{exp,name}=>format!(r#".and_then({}.named("{}"))"#,exp.to_inner_code(CodeState::first()),name),
+                Pattern::TextEnd => ".must_end_text()".to_string(),
+                Pattern::Named{exp,name}=>format!(r#".and_then({}.named("{}"))"#,exp.to_inner_code(CodeState::root()),name),
                 _ => format!(".and_then({})", self.to_inner_code(CodeState::first())),
             }
         }
     }
 
+    /// Render the final regular expression: this is the canonical way to get the regex
+    /// to compile, `to_string` only gives the same output for backward compatibility
+    pub fn to_regex_string(&self) -> String {
+        self.render(RenderOptions::current()).to_string()
+    }
+
+    /// Rust source for a module with a function returning this pattern,
+    /// built with the code from `to_code`
+    pub fn to_module(&self, fn_name: &str) -> String {
+        format!(
+            "use ezregexp::*;\n\npub fn {}() -> Pattern {{\n    {}\n}}\n",
+            fn_name,
+            self.to_code()
+        )
+    }
+
+    /// Render the pattern with the given options
+    fn render(&self, options: RenderOptions) -> Render<'_> {
+        Render {
+            pattern: self,
+            options,
+        }
+    }
+
+    /// Render the pattern with all groups non-capturing, named groups included.
+    /// Useful when only checking for a match, since capture groups add overhead
+    pub fn to_string_uncaptured(&self) -> String {
+        self.render(RenderOptions {
+            uncaptured: true,
+            ..RenderOptions::current()
+        })
+        .to_string()
+    }
+
+    /// Render the pattern with forward slashes escaped as `\/`, for regex literals
+    /// delimited by slashes like in JavaScript or sed. The `regex` crate rejects `\/`,
+    /// so only use this output with other engines
+    pub fn to_string_escaping_slashes(&self) -> String {
+        self.render(RenderOptions {
+            escape_slashes: true,
+            ..RenderOptions::current()
+        })
+        .to_string()
+    }
+
+    /// Render the pattern in verbose mode (`(?x)`), one top-level element per line,
+    /// with a comment giving the names of the groups captured on each line
+    pub fn to_verbose(&self) -> String {
+        let o = RenderOptions {
+            verbose: true,
+            ..RenderOptions::current()
+        };
+        let exps = match self {
+            Pattern::Sequence(exps) => exps.iter().collect(),
+            _ => vec![self],
+        };
+        let mut s = String::from("(?x)\n");
+        for e in exps.iter() {
+            let line = match e {
+                Pattern::Or(..) if exps.len() > 1 => format!("{}{})", o.open(), e.render(o)),
+                _ => e.render(o).to_string(),
+            };
+            s.push_str(&line);
+            let mut names = vec![];
+            e.group_names(&mut names);
+            if !names.is_empty() {
+                s.push_str(&format!("  # capture: {}", names.join(", ")));
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Names of the named groups in the pattern, in order
+    fn group_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        if let Pattern::Named { name, .. } = self {
+            names.push(name);
+        }
+        for c in self.children() {
+            c.group_names(names);
+        }
+    }
+
+    /// Render the pattern with the branches of alternatives sorted, so the same alternatives
+    /// always give the same regex whatever the order they were added in.
+    /// The regex engine tries branches in order, so with overlapping branches (`a|ab`)
+    /// this may change what is matched: only use it when order doesn't matter, like in snapshots
+    pub fn to_string_deterministic(&self) -> String {
+        self.clone().normalize().sort_branches().to_string()
+    }
+
+    /// Sort alternatives by their rendered regex, recursively
+    fn sort_branches(self) -> Self {
+        match self.map(Pattern::sort_branches) {
+            Pattern::Or(mut exps) => {
+                exps.sort_by_cached_key(|e| e.to_string());
+                Pattern::Or(exps)
+            }
+            p => p,
+        }
+    }
+
+    /// Render the regex with the trailing quantifier greedy, then lazy,
+    /// to compare matching behaviors
+    pub fn both_greediness(&self) -> (String, String) {
+        (
+            self.clone().trailing_lazy(false).to_string(),
+            self.clone().trailing_lazy(true).to_string(),
+        )
+    }
+
+    /// Set the laziness of the trailing quantifier, if any
+    fn trailing_lazy(mut self, lazy: bool) -> Self {
+        match &mut self {
+            Pattern::Sequence(exps) => {
+                if let Some(Pattern::Many { lazy: l, .. }) = exps.last_mut() {
+                    *l = lazy;
+                }
+            }
+            Pattern::Many { lazy: l, .. } => *l = lazy,
+            _ => {}
+        }
+        self
+    }
+
+    /// Content of a character class matching the same characters, for patterns
+    /// that don't have a negated shorthand: the characters of a text, a range, a control character,
+    /// or an alternation of these
+    pub(crate) fn class_items(&self) -> Option<String> {
+        match self {
+            Pattern::Or(exps) if !exps.is_empty() => exps.iter().map(Pattern::class_items).collect(),
+            Pattern::Text(t) if !t.is_empty() => Some(t.chars().map(class_escape).collect()),
+            Pattern::CharRange { from, to } => {
+                Some(format!("{}-{}", class_escape(*from), class_escape(*to)))
+            }
+            Pattern::Control(c) => Some(control_escape(*c)),
+            Pattern::HexChar(code) => Some(hex_escape(*code)),
+            Pattern::CharSet {
+                items,
+                negated: false,
+            } => Some(items.iter().map(ClassItem::class_text).collect()),
+            _ => None,
+        }
+    }
+
+    /// Is the pattern an alternation of character ranges and sets? It is rendered as a single
+    /// class: `either((char_range('a', 'z'), one_of(['_'])))` gives `[a-z_]`
+    pub(crate) fn is_class_union(&self) -> bool {
+        match self {
+            Pattern::Or(exps) => {
+                !exps.is_empty()
+                    && exps.iter().all(|e| {
+                        matches!(
+                            e,
+                            Pattern::CharRange { .. } | Pattern::CharSet { negated: false, .. }
+                        )
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// Can the pattern be negated with `any_except`?
+    pub(crate) fn can_negate(&self) -> bool {
+        matches!(
+            self,
+            Pattern::Digit
+                | Pattern::Letter
+                | Pattern::Number
+                | Pattern::WordCharacter
+                | Pattern::Whitespace
+                | Pattern::PosixClass(..)
+        ) || self.class_items().is_some()
+    }
+
+    /// How the pattern is grouped before a quantifier is applied. Alternations are put in
+    /// a non capturing group, since the group is only there to delimit the branches
+    pub(crate) fn quantified_group(&self) -> QuantifiedGroup {
+        match self {
+            Pattern::Or(..) if self.is_class_union() => QuantifiedGroup::Bare,
+            Pattern::Or(..) => QuantifiedGroup::NonCapturing,
+            _ if self.needs_group() => QuantifiedGroup::Capturing,
+            _ => QuantifiedGroup::Bare,
+        }
+    }
+
+    /// Does the pattern need to be grouped before a quantifier is applied?
+    /// Single characters, classes and groups are atoms, and don't
+    pub(crate) fn needs_group(&self) -> bool {
+        match self {
+            Pattern::Sequence(exps) => match exps.as_slice() {
+                [exp] => exp.needs_group(),
+                exps => exps.len() > 1,
+            },
+            Pattern::Text(t) => t.chars().count() > 1,
+            Pattern::Or(..) => !self.is_class_union(),
+            Pattern::Many { .. } => true,
+            Pattern::Curated { exp, .. } => exp.needs_group(),
+            // raw fragments are opaque, parse them to know if they are a single atom
+            Pattern::Raw(r) => match Parser::new().parse(r) {
+                Ok(Ast::Literal(..))
+                | Ok(Ast::Dot(..))
+                | Ok(Ast::Class(..))
+                | Ok(Ast::Group(..))
+                | Ok(Ast::Empty(..)) => false,
+                // a lone backreference like `\1`
+                Err(e) => {
+                    !(*e.kind() == ErrorKind::UnsupportedBackreference
+                        && e.span().start.offset == 0
+                        && e.span().end.offset == r.len())
+                }
+                _ => true,
+            },
+            _ => false,
+        }
+    }
+
+    /// Code for the pattern used as an argument of a builder function
+    pub(crate) fn to_argument_code(&self) -> String {
+        self.to_inner_code(CodeState::first())
+    }
+
     /// Chain an alternative
     pub fn and_either<PL: PatternList>(self, branches: PL) -> Self {
         self.push(Pattern::Or(branches.into_patterns().collect()))
     }
 
+    /// Add an alternative: the branches of an alternation are extended, other patterns
+    /// become the first branch of a new alternation
+    pub fn or_else<T: Into<Pattern>>(self, exp: T) -> Self {
+        match self {
+            Pattern::Or(mut exps) => {
+                exps.push(exp.into());
+                Pattern::Or(exps)
+            }
+            _ => Pattern::Or(vec![self, exp.into()]),
+        }
+    }
+
     /// Append a new pattern
     pub fn and_then<T: Into<Pattern>>(self, exp: T) -> Self {
         self.push(exp.into())
@@ -266,6 +1031,7 @@ impl Pattern {
             exp: Box::new(exp.into()),
             low: 0,
             high: 1,
+            lazy: false,
         })
     }
 
@@ -275,6 +1041,7 @@ impl Pattern {
             exp: Box::new(exp.into()),
             low: 0,
             high: 0,
+            lazy: false,
         })
     }
 
@@ -284,58 +1051,302 @@ impl Pattern {
             exp: Box::new(exp.into()),
             low: 1,
             high: 0,
+            lazy: false,
         })
     }
 
     /// Repeat the previous pattern between low and high times
     pub fn many(self, low: u32, high: u32) -> Self {
+        self.wrap_last(|e| Pattern::Many {
+            exp: Box::new(e),
+            low,
+            high,
+            lazy: false,
+        })
+    }
+
+    /// Repeat the previous pattern with the given bounds
+    pub fn repeat(self, repeat: Repeat) -> Self {
+        let (low, high) = repeat.bounds();
+        self.many(low, high)
+    }
+
+    /// Repeat the previous pattern n times. Panics if n is 0
+    pub fn times(self, n: u32) -> Self {
+        self.repeat(Repeat::Exactly(n))
+    }
+
+    /// Add a range of characters, inclusive, to a character class built with `char_class`
+    /// or `one_of`. Panics if the pattern is not a character class or if `from` is after `to`
+    pub fn range(self, from: char, to: char) -> Self {
+        if from > to {
+            panic!("{}", InvalidRange { from, to });
+        }
+        self.add_class_items(std::iter::once(ClassItem::Range(from, to)))
+    }
+
+    /// Add characters to a character class built with `char_class` or `one_of`.
+    /// Panics if the pattern is not a character class
+    pub fn chars<I: IntoIterator<Item = char>>(self, chars: I) -> Self {
+        self.add_class_items(chars.into_iter().map(ClassItem::Char))
+    }
+
+    /// Add items to a character class
+    fn add_class_items<I: IntoIterator<Item = ClassItem>>(self, new_items: I) -> Self {
         match self {
-            Pattern::Sequence(mut exps) if exps.len() > 0 => {
-                let e = exps.pop().unwrap();
-                exps.push(Pattern::Many {
-                    exp: Box::new(e),
-                    low: low,
-                    high: high,
-                });
-                Pattern::Sequence(exps)
+            Pattern::CharSet { mut items, negated } => {
+                items.extend(new_items);
+                Pattern::CharSet { items, negated }
             }
-            _ => Pattern::Many {
-                exp: Box::new(self),
-                low: low,
-                high: high,
-            },
+            p => panic!("not a character class: {}", p),
         }
     }
 
-    /// Repeat the previous pattern n times
-    pub fn times(self, n: u32) -> Self {
-        self.many(n, n)
+    /// Make the previous pattern optional: `?`.
+    /// `start_with("colo").and_then("u").optional().and_then("r")` gives `colou?r`
+    pub fn optional(self) -> Self {
+        self.many(0, 1)
+    }
+
+    /// Repeat the previous pattern one or more times: `+`
+    pub fn one_or_more(self) -> Self {
+        self.many(1, 0)
+    }
+
+    /// Repeat the previous pattern zero or more times: `*`
+    pub fn zero_or_more(self) -> Self {
+        self.many(0, 0)
+    }
+
+    /// Repeat the previous pattern at least n times: `{n,}`
+    pub fn at_least(self, n: u32) -> Self {
+        self.many(n, 0)
+    }
+
+    /// Repeat the previous pattern at most n times: `{0,n}`. Panics if n is 0
+    pub fn at_most(self, n: u32) -> Self {
+        assert!(n > 0, "at_most needs a bound of at least 1");
+        self.many(0, n)
+    }
+
+    /// Repeat the previous pattern, separated by the given separator, at least once:
+    /// `digit().separated_by(",")` gives `\d(?:,\d)*`. Like `list_of`, but chained
+    pub fn separated_by<S: Into<Pattern>>(self, sep: S) -> Self {
+        let sep = sep.into();
+        self.wrap_last(|item| {
+            let code = format!("{}.separated_by({})", item.to_code(), sep.to_argument_code());
+            Pattern::Curated {
+                exp: Box::new(separated(item, sep)),
+                code,
+            }
+        })
     }
 
     /// name preceding pattern
     pub fn named<S: Into<String>>(self, name: S) -> Self {
+        let name = name.into();
+        self.wrap_last(|e| Pattern::Named {
+            exp: Box::new(e),
+            name,
+        })
+    }
+
+    /// Capture preceding pattern in a numbered group
+    pub fn captured(self) -> Self {
+        self.wrap_last(|e| Pattern::Group {
+            exp: Box::new(e),
+            capturing: true,
+        })
+    }
+
+    /// Apply a wrapper to the last pattern of a sequence, or to self
+    fn wrap_last<F: FnOnce(Pattern) -> Pattern>(self, wrap: F) -> Self {
         match self {
-            Pattern::Sequence(mut exps) if exps.len() > 0 => {
+            Pattern::Sequence(mut exps) if !exps.is_empty() => {
                 let e = exps.pop().unwrap();
-                exps.push(Pattern::Named {
-                    exp: Box::new(e),
-                    name: name.into(),
-                });
+                exps.push(wrap(e));
                 Pattern::Sequence(exps)
             }
-            _ => Pattern::Named {
+            _ => wrap(self),
+        }
+    }
+
+    /// Match the whole pattern ignoring case: `(?i:exp)`
+    pub fn case_insensitive(self) -> Self {
+        self.with_flag('i')
+    }
+
+    /// Let `^` and `$` match at the start and end of lines in the whole pattern: `(?m:exp)`
+    pub fn multiline(self) -> Self {
+        self.with_flag('m')
+    }
+
+    /// Let `.` match new lines in the whole pattern: `(?s:exp)`
+    pub fn dotall(self) -> Self {
+        self.with_flag('s')
+    }
+
+    /// Match the whole pattern with the given inline flags, merging with existing flags:
+    /// `flags("ms")` gives `(?ms:exp)`. Known flags are `i`, `m`, `s`, `x`, `U` and `u`
+    pub fn flags(self, flags: &str) -> Self {
+        assert!(
+            flags.chars().all(|c| FLAGS.contains(c)),
+            "unknown flag in {:?}",
+            flags
+        );
+        flags.chars().fold(self, |p, flag| p.with_flag(flag))
+    }
+
+    /// Match the whole pattern with the given inline flags turned off, merging with existing
+    /// flags: `without_flags("i")` gives `(?-i:exp)`
+    pub fn without_flags(self, flags: &str) -> Self {
+        assert!(
+            flags.chars().all(|c| FLAGS.contains(c)),
+            "unknown flag in {:?}",
+            flags
+        );
+        flags
+            .chars()
+            .fold(self, |p, flag| p.with_flag_state(flag, false))
+    }
+
+    /// Wrap the pattern with the given flag, merging with existing flags
+    fn with_flag(self, flag: char) -> Self {
+        self.with_flag_state(flag, true)
+    }
+
+    /// Wrap the pattern with the given flag turned on or off, merging with existing flags.
+    /// Flags are written `on-off`, and a flag already set by the wrapped pattern wins
+    fn with_flag_state(self, flag: char, on: bool) -> Self {
+        match self {
+            Pattern::Flags { mut flags, exp } => {
+                if !flags.contains(flag) {
+                    match (on, flags.find('-')) {
+                        (true, Some(pos)) => flags.insert(pos, flag),
+                        (true, None) | (false, Some(_)) => flags.push(flag),
+                        (false, None) => {
+                            flags.push('-');
+                            flags.push(flag);
+                        }
+                    }
+                }
+                Pattern::Flags { flags, exp }
+            }
+            _ => Pattern::Flags {
+                flags: if on {
+                    flag.to_string()
+                } else {
+                    format!("-{}", flag)
+                },
                 exp: Box::new(self),
-                name: name.into(),
             },
         }
     }
 
+    /// Make the preceding repetition lazy, so it matches as few times as possible
+    pub fn lazy(self) -> Self {
+        self.trailing_lazy(true)
+    }
+
     /// Must reach end of input
     pub fn must_end(self) -> Self {
         self.push(Pattern::InputEnd)
     }
 
-    /*fn from_list(mut exprs: Vec<Pattern>) -> Pattern {
+    /// Must reach end of text: `\z`. Unlike `must_end`, this does not match at the end
+    /// of a line in multi-line mode
+    pub fn must_end_text(self) -> Self {
+        self.push(Pattern::TextEnd)
+    }
+
+    /// Direct sub-patterns of this pattern
+    pub fn children(&self) -> Vec<&Pattern> {
+        match self {
+            Pattern::Sequence(exps) | Pattern::Or(exps) => exps.iter().collect(),
+            Pattern::Many { exp, .. }
+            | Pattern::Not(exp)
+            | Pattern::Named { exp, .. }
+            | Pattern::Curated { exp, .. }
+            | Pattern::Group { exp, .. }
+            | Pattern::LookAhead { exp, .. }
+            | Pattern::LookBehind { exp, .. }
+            | Pattern::Flags { exp, .. } => vec![exp],
+            _ => vec![],
+        }
+    }
+
+    /// Replace the inner pattern of the groups with the given name, keeping the name.
+    /// Curated helpers are left untouched
+    pub fn replace_named(self, name: &str, replacement: Pattern) -> Self {
+        self.replace_named_inner(name, &replacement)
+    }
+
+    /// Replace named groups using a borrowed replacement
+    fn replace_named_inner(self, name: &str, replacement: &Pattern) -> Self {
+        match self {
+            Pattern::Named { name: n, .. } if n == name => Pattern::Named {
+                exp: Box::new(replacement.clone()),
+                name: n,
+            },
+            Pattern::Curated { .. } => self,
+            _ => self.map(|e| e.replace_named_inner(name, replacement)),
+        }
+    }
+
+    /// Apply a transformation to the direct sub-patterns of this pattern
+    pub(crate) fn map<F: FnMut(Pattern) -> Pattern>(self, mut f: F) -> Self {
+        match self {
+            Pattern::Sequence(exps) => Pattern::Sequence(exps.into_iter().map(f).collect()),
+            Pattern::Or(exps) => Pattern::Or(exps.into_iter().map(f).collect()),
+            Pattern::Many {
+                exp,
+                low,
+                high,
+                lazy,
+            } => Pattern::Many {
+                exp: Box::new(f(*exp)),
+                low,
+                high,
+                lazy,
+            },
+            Pattern::Not(exp) => Pattern::Not(Box::new(f(*exp))),
+            Pattern::Named { exp, name } => Pattern::Named {
+                exp: Box::new(f(*exp)),
+                name,
+            },
+            Pattern::Curated { exp, code } => Pattern::Curated {
+                exp: Box::new(f(*exp)),
+                code,
+            },
+            Pattern::Group { exp, capturing } => Pattern::Group {
+                exp: Box::new(f(*exp)),
+                capturing,
+            },
+            Pattern::LookAhead { exp, negated } => Pattern::LookAhead {
+                exp: Box::new(f(*exp)),
+                negated,
+            },
+            Pattern::LookBehind { exp, negated } => Pattern::LookBehind {
+                exp: Box::new(f(*exp)),
+                negated,
+            },
+            Pattern::Flags { flags, exp } => Pattern::Flags {
+                flags,
+                exp: Box::new(f(*exp)),
+            },
+            _ => self,
+        }
+    }
+
+    /// Number of top-level elements: the length of a sequence, 1 for any other pattern
+    pub fn sequence_len(&self) -> usize {
+        match self {
+            Pattern::Sequence(exps) => exps.len(),
+            _ => 1,
+        }
+    }
+
+    /*fn from_list(mut exprs: Vec<Pattern>) -> Pattern {
         if exprs.len()==1 {
             exprs.pop().unwrap()
         } else {
@@ -355,11 +1366,30 @@ impl Pattern {
     }
 }
 
+/// Inline flags
+const FLAGS: &str = "imsxUu";
+
 /// Start of input
 pub fn at_start() -> Pattern {
     Pattern::InputStart
 }
 
+/// Start of text: `\A`. Unlike `at_start`, this does not match at the start of a line
+/// in multi-line mode
+pub fn at_text_start() -> Pattern {
+    Pattern::TextStart
+}
+
+/// Word boundary: `\b`
+pub fn word_boundary() -> Pattern {
+    Pattern::WordBoundary { negated: false }
+}
+
+/// Not a word boundary: `\B`
+pub fn not_word_boundary() -> Pattern {
+    Pattern::WordBoundary { negated: true }
+}
+
 /// Start matching with given pattern
 pub fn start_with<T: Into<Pattern>>(exp: T) -> Pattern {
     exp.into()
@@ -370,16 +1400,64 @@ pub fn text(text: &str) -> Pattern {
     Pattern::Text(text.to_owned())
 }
 
+/// Insert the given regular expression fragment as is, without escaping.
+/// Use `escape` on any dynamic part of the fragment
+pub fn raw(fragment: &str) -> Pattern {
+    Pattern::Raw(fragment.to_owned())
+}
+
+/// Characters with a special meaning in a regular expression, outside of classes
+pub(crate) const TEXT_META_CHARACTERS: &str = r".+*?()[]{}^$|\";
+
+/// Escape the characters of a text that have a special meaning in a regular expression
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if TEXT_META_CHARACTERS.contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape all regular expression meta characters in the text,
+/// so it can be safely used in a `raw` fragment
+pub fn escape(text: &str) -> String {
+    regex::escape(text)
+}
+
 /// Match a digit
 pub fn digit() -> Pattern {
     Pattern::Digit
 }
 
+/// Capture the given pattern in a numbered group: `(exp)`
+pub fn capture<T: Into<Pattern>>(exp: T) -> Pattern {
+    Pattern::Group {
+        exp: Box::new(exp.into()),
+        capturing: true,
+    }
+}
+
+/// Group the given pattern without capturing it: `(?:exp)`
+pub fn group<T: Into<Pattern>>(exp: T) -> Pattern {
+    Pattern::Group {
+        exp: Box::new(exp.into()),
+        capturing: false,
+    }
+}
+
 /// Match a letter
 pub fn letter() -> Pattern {
     Pattern::Letter
 }
 
+/// Match a Unicode number: `\pN`. Unlike `digit()`, this includes numerals like `Ⅻ` or `½`
+pub fn number() -> Pattern {
+    Pattern::Number
+}
+
 /// Match anything
 pub fn anything() -> Pattern {
     Pattern::Any
@@ -390,8 +1468,267 @@ pub fn word_character() -> Pattern {
     Pattern::WordCharacter
 }
 
+/// Match a whitespace character
+pub fn whitespace() -> Pattern {
+    Pattern::Whitespace
+}
+
+/// Match the control character written `\cX` in other engines, given its letter.
+/// It is rendered as the equivalent hexadecimal escape, since `regex` does not
+/// support the `\cX` syntax: `control_char('A')` produces `\x01`
+pub fn control_char(letter: char) -> Pattern {
+    assert!(
+        letter.is_ascii_alphabetic(),
+        "control character letter must be an ASCII letter"
+    );
+    Pattern::Control(((letter.to_ascii_uppercase() as u8) ^ 0x40) as char)
+}
+
+/// Match the character with the given code point, written as a hexadecimal escape:
+/// `hex_char(0x41)` gives `\x41` and `hex_char(0x1F600)` gives `\x{1F600}`.
+/// Panics if the code point is not a Unicode scalar value
+pub fn hex_char(code: u32) -> Pattern {
+    assert!(
+        std::char::from_u32(code).is_some(),
+        "invalid code point: 0x{:X}",
+        code
+    );
+    Pattern::HexChar(code)
+}
+
+/// Match the text captured earlier by the group with the given index, starting at 1: `\1`.
+/// The `regex` crate does not support backreferences: `compile` fails, use another engine
+/// to run it. Panics if the index is 0
+pub fn backref(index: u32) -> Pattern {
+    assert!(index > 0, "backreference group index must start at 1");
+    Pattern::BackRef(BackRefKind::Index(index))
+}
+
+/// Match the text captured earlier by the group with the given name: `\k<name>`.
+/// The `regex` crate does not support backreferences: `compile` fails, use another engine
+/// to run it
+pub fn backref_name(name: &str) -> Pattern {
+    Pattern::BackRef(BackRefKind::Name(name.to_owned()))
+}
+
+/// Match a tab character: `\t`
+pub fn tab() -> Pattern {
+    Pattern::Control('\t')
+}
+
+/// Match a line feed character: `\n`
+pub fn newline() -> Pattern {
+    Pattern::Control('\n')
+}
+
+/// Match a carriage return character: `\r`
+pub fn carriage_return() -> Pattern {
+    Pattern::Control('\r')
+}
+
+/// Match a character between the given characters, inclusive: `char_range('a', 'z')` gives `[a-z]`.
+/// Panics if `from` is after `to`, see `try_char_range`
+pub fn char_range(from: char, to: char) -> Pattern {
+    match try_char_range(from, to) {
+        Ok(p) => p,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Match a character between the given characters, inclusive, failing if `from` is after `to`
+pub fn try_char_range(from: char, to: char) -> std::result::Result<Pattern, InvalidRange> {
+    if from <= to {
+        Ok(Pattern::CharRange { from, to })
+    } else {
+        Err(InvalidRange { from, to })
+    }
+}
+
+/// A character range whose start is after its end
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidRange {
+    pub from: char,
+    pub to: char,
+}
+
+impl Display for InvalidRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "invalid range: {:?} is after {:?}", self.from, self.to)
+    }
+}
+
+impl std::error::Error for InvalidRange {}
+
+/// Match one of the given characters: `one_of(['a', 'b', 'c'])` gives `[abc]`
+pub fn one_of<I: IntoIterator<Item = char>>(chars: I) -> Pattern {
+    Pattern::CharSet {
+        items: chars.into_iter().map(ClassItem::Char).collect(),
+        negated: false,
+    }
+}
+
+/// Start an empty character class, to add ranges and characters to:
+/// `char_class().range('a', 'c').chars(['x', 'y', 'z'])` gives `[a-cxyz]`.
+/// A class without items can't be compiled
+pub fn char_class() -> Pattern {
+    Pattern::CharSet {
+        items: vec![],
+        negated: false,
+    }
+}
+
+/// Code for the characters of class items, ranges are skipped
+fn chars_code(items: &[ClassItem]) -> String {
+    items
+        .iter()
+        .filter_map(|i| match i {
+            ClassItem::Char(c) => Some(format!("{:?}", c)),
+            ClassItem::Range(..) => None,
+        })
+        .join(", ")
+}
+
+/// Escape a character for use in a class, if needed
+pub(crate) fn class_escape(c: char) -> String {
+    if regex_syntax::is_meta_character(c) {
+        format!("\\{}", c)
+    } else {
+        c.to_string()
+    }
+}
+
+/// Items separated by a separator: `item(?:sep item)*`
+pub(crate) fn separated(item: Pattern, sep: Pattern) -> Pattern {
+    Pattern::Sequence(vec![
+        item.clone(),
+        Pattern::Many {
+            exp: Box::new(Pattern::Group {
+                exp: Box::new(Pattern::Sequence(vec![sep, item])),
+                capturing: false,
+            }),
+            low: 0,
+            high: 0,
+            lazy: false,
+        },
+    ])
+}
+
+/// Escape a control character, using the usual escapes for tabs and line endings
+fn control_escape(c: char) -> String {
+    match c {
+        '\t' => r"\t".to_string(),
+        '\n' => r"\n".to_string(),
+        '\r' => r"\r".to_string(),
+        c => format!(r"\x{:02X}", c as u32),
+    }
+}
+
+/// Hexadecimal escape of a code point, braced if it does not fit in two digits
+fn hex_escape(code: u32) -> String {
+    if code <= 0xFF {
+        format!(r"\x{:02X}", code)
+    } else {
+        format!(r"\x{{{:X}}}", code)
+    }
+}
+
+/// POSIX character classes supported by the regex syntax
+const POSIX_CLASSES: [&str; 14] = [
+    "alnum", "alpha", "ascii", "blank", "cntrl", "digit", "graph", "lower", "print", "punct",
+    "space", "upper", "word", "xdigit",
+];
+
+/// Match a POSIX character class given its name, like `alpha` for `[[:alpha:]]`.
+/// These classes only match ASCII characters, use `any_except` to negate them
+pub fn posix_class(name: &str) -> Pattern {
+    assert!(
+        POSIX_CLASSES.contains(&name),
+        "unknown POSIX class: {}",
+        name
+    );
+    Pattern::PosixClass(name.to_owned())
+}
+
+/// Match a character of the given Unicode class: a general category like `Lu` for
+/// uppercase letters or `P` for punctuation, a script like `Greek`, or any other property
+/// supported by the regex syntax. `unicode_category("Lu")` gives `\p{Lu}`.
+/// Panics if the name is not known
+pub fn unicode_category(name: &str) -> Pattern {
+    assert!(
+        regex_syntax::Parser::new()
+            .parse(&format!(r"\p{{{}}}", name))
+            .is_ok(),
+        "unknown Unicode class: {}",
+        name
+    );
+    Pattern::UnicodeClass {
+        name: name.to_owned(),
+        negated: false,
+    }
+}
+
+/// Match any character except the given one. The pattern must be a character class
+/// like `digit()`, the characters of a text, a set, a range, a control character or an
+/// alternation of these, `any_except(either((char_range('a', 'z'), "_")))` giving `[^a-z_]`: other
+/// patterns can't be negated, `validate` warns about them and `compile` fails
 pub fn any_except<T: Into<Pattern>>(exp: T) -> Pattern {
-    Pattern::Not(Box::new(exp.into()))
+    match exp.into() {
+        Pattern::CharSet { items, negated } => Pattern::CharSet {
+            items,
+            negated: !negated,
+        },
+        Pattern::UnicodeClass { name, negated } => Pattern::UnicodeClass {
+            name,
+            negated: !negated,
+        },
+        exp => Pattern::Not(Box::new(exp)),
+    }
+}
+
+/// Assert that the given pattern follows, without consuming it: `(?=exp)`.
+/// The `regex` crate does not support lookaround, use another engine to run it
+pub fn followed_by<T: Into<Pattern>>(exp: T) -> Pattern {
+    Pattern::LookAhead {
+        exp: Box::new(exp.into()),
+        negated: false,
+    }
+}
+
+/// Assert that the given pattern does not follow: `(?!exp)`.
+/// The `regex` crate does not support lookaround, use another engine to run it
+pub fn not_followed_by<T: Into<Pattern>>(exp: T) -> Pattern {
+    Pattern::LookAhead {
+        exp: Box::new(exp.into()),
+        negated: true,
+    }
+}
+
+/// Assert that the given pattern precedes, without consuming it: `(?<=exp)`.
+/// The `regex` crate does not support lookaround: `compile` fails, use another engine to run it
+pub fn preceded_by<T: Into<Pattern>>(exp: T) -> Pattern {
+    Pattern::LookBehind {
+        exp: Box::new(exp.into()),
+        negated: false,
+    }
+}
+
+/// Assert that the given pattern does not precede: `(?<!exp)`.
+/// The `regex` crate does not support lookaround: `compile` fails, use another engine to run it
+pub fn not_preceded_by<T: Into<Pattern>>(exp: T) -> Pattern {
+    Pattern::LookBehind {
+        exp: Box::new(exp.into()),
+        negated: true,
+    }
+}
+
+/// Match the given pattern optionally
+pub fn maybe<T: Into<Pattern>>(exp: T) -> Pattern {
+    Pattern::Many {
+        exp: Box::new(exp.into()),
+        low: 0,
+        high: 1,
+        lazy: false,
+    }
 }
 
 /// Match any of the given patterns
@@ -411,29 +1748,35 @@ impl PatternList for Vec<Pattern> {
     }
 }
 
-/// Convert a tuple of patterns into an iterator
-impl<T1, T2> PatternList for (T1, T2)
-where
-    T1: Into<Pattern>,
-    T2: Into<Pattern>,
-{
+/// Convert an array of patterns into an iterator
+impl<T: Into<Pattern>, const N: usize> PatternList for [T; N] {
     fn into_patterns(self) -> Box<dyn Iterator<Item = Pattern>> {
-        Box::new(vec![self.0.into(), self.1.into()].into_iter())
+        let patterns: Vec<Pattern> = IntoIterator::into_iter(self).map(Into::into).collect();
+        Box::new(patterns.into_iter())
     }
 }
 
-/// Convert a tripler of patterns into an iterator
-impl<T1, T2, T3> PatternList for (T1, T2, T3)
-where
-    T1: Into<Pattern>,
-    T2: Into<Pattern>,
-    T3: Into<Pattern>,
-{
-    fn into_patterns(self) -> Box<dyn Iterator<Item = Pattern>> {
-        Box::new(vec![self.0.into(), self.1.into(), self.2.into()].into_iter())
-    }
+/// Implement `PatternList` for a tuple of the given types and indices
+macro_rules! tuple_pattern_list {
+    ($($t:ident $i:tt),+) => {
+        /// Convert a tuple of patterns into an iterator
+        impl<$($t),+> PatternList for ($($t,)+)
+        where
+            $($t: Into<Pattern>,)+
+        {
+            fn into_patterns(self) -> Box<dyn Iterator<Item = Pattern>> {
+                Box::new(vec![$(self.$i.into()),+].into_iter())
+            }
+        }
+    };
 }
 
+tuple_pattern_list!(T1 0, T2 1);
+tuple_pattern_list!(T1 0, T2 1, T3 2);
+tuple_pattern_list!(T1 0, T2 1, T3 2, T4 3);
+tuple_pattern_list!(T1 0, T2 1, T3 2, T4 3, T5 4);
+tuple_pattern_list!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -468,7 +1811,7 @@ mod tests {
                 .must_end()
                 .to_string()
         );
-        assert_eq!(r#"\D\PN\W"#,any_except(digit()).and_then(any_except(letter())).and_then(any_except(word_character())).to_string());
+        assert_eq!(r#"\D\PL\W"#,any_except(digit()).and_then(any_except(letter())).and_then(any_except(word_character())).to_string());
         assert_eq!(
             r"(?P<y>\d{4})-(?P<m>\d{2})-(?P<d>\d{2})",
                 start_with(digit()
@@ -484,6 +1827,11 @@ mod tests {
                     .named("d"))
                 .to_string()
         );
+        assert_eq!(r"\x01", control_char('A').to_string());
+        assert_eq!(r"(\d{4})", digit().times(4).captured().to_string());
+        assert_eq!("(ab)+", text("ab").captured().one_or_more().to_string());
+        assert_eq!("a?bc", maybe("a").and_then("bc").to_string());
+        assert_eq!(r"a\x1A", start_with("a").and_then(control_char('z')).to_string());
     }
 
     #[test]
@@ -518,5 +1866,909 @@ mod tests {
         );
         assert_eq!(r#"any_except(digit()).and_then(any_except(letter())).and_then(any_except(word_character()))"#,any_except(digit()).and_then(any_except(letter())).and_then(any_except(word_character())).to_code());
         assert_eq!(r#"start_with(digit().times(4).named("y")).and_then("-").and_then(digit().times(2).named("m")).and_then("-").and_then(digit().times(2).named("d"))"#,start_with(digit().times(4).named("y")).and_then("-").and_then(digit().times(2).named("m")).and_then("-").and_then(digit().times(2).named("d")).to_code());
+        assert_eq!(r#"control_char('A')"#, control_char('a').to_code());
+        assert_eq!(r#"maybe("a").and_then("bc")"#, maybe("a").and_then("bc").to_code());
+        assert_eq!(r#"maybe(digit())"#, maybe(digit()).to_code());
+        assert_eq!("digit().times(4).captured()", digit().times(4).captured().to_code());
+        assert_eq!(r#"text("ab").times(2)"#, text("ab").times(2).to_code());
+        assert_eq!(r#"start_with("a").and_then(control_char('Z'))"#, start_with("a").and_then(control_char('Z')).to_code());
+    }
+
+    #[test]
+    fn test_sequence_len() {
+        let date = start_with(digit().times(4).named("y"))
+            .and_then("-")
+            .and_then(digit().times(2).named("m"))
+            .and_then("-")
+            .and_then(digit().times(2).named("d"));
+        assert_eq!(5, date.sequence_len());
+        assert_eq!(5, date.children().len());
+        assert_eq!(1, digit().times(4).sequence_len());
+        assert_eq!(1, text("Handel").sequence_len());
+    }
+
+    #[test]
+    fn test_one_of() {
+        assert_eq!("[abc]", one_of(['a', 'b', 'c']).to_string());
+        assert_eq!("[^ab]", any_except(one_of(['a', 'b'])).to_string());
+        assert_eq!(r"[\]\^\\\-]", one_of([']', '^', '\\', '-']).to_string());
+        assert_eq!("one_of(['a', 'b'])", one_of(['a', 'b']).to_code());
+        assert_eq!(
+            "any_except(one_of(['a', 'b'])).and_then(one_of(['-', '+'])).times(2)",
+            any_except(one_of(['a', 'b']))
+                .and_then(one_of("-+".chars()).times(2))
+                .to_code()
+        );
+        assert_eq!("[xy]{2}", one_of(vec!['x', 'y']).times(2).to_string());
+        let re = regex::Regex::new(&format!("^{}$", one_of([']', '^', '\\', '-']).one_or_more()))
+            .unwrap();
+        assert!(re.is_match(r"]^\-"));
+        assert!(!re.is_match("a"));
+    }
+
+    #[test]
+    fn test_any_except() {
+        assert_eq!("[^a]", any_except(text("a")).to_string());
+        assert_eq!(r"[^abc\]]", any_except("abc]").to_string());
+        assert_eq!("[^a-z]+", any_except(char_range('a', 'z')).one_or_more().to_string());
+        assert_eq!(r"[^\x01]", any_except(control_char('A')).to_string());
+        assert_eq!(r#"any_except("a").and_then("b")"#, any_except("a").and_then("b").to_code());
+        let p = any_except(digit().one_or_more()).one_or_more();
+        assert_eq!(
+            Some(r"pattern can't be negated: \d+".to_string()),
+            p.compile().err().map(|e| e.to_string())
+        );
+        assert!(p.compile_bytes().is_err());
+        let re = regex::Regex::new(&format!("^{}+$", any_except("\"'"))).unwrap();
+        assert!(re.is_match("abc"));
+        assert!(!re.is_match("a'c"));
+    }
+
+    #[test]
+    fn test_class_union() {
+        let p = either((char_range('a', 'z'), char_range('0', '9'), one_of(['_'])));
+        assert_eq!("[a-z0-9_]", p.to_string());
+        assert_eq!("[a-z0-9_]+", p.clone().many(1, 0).to_string());
+        assert_eq!("x[a-z0-9_]y", start_with("x").and_then(p.clone()).and_then("y").to_string());
+        assert_eq!("x(a|[0-9])y", start_with("x").and_either(("a", char_range('0', '9'))).and_then("y").to_string());
+        let re = at_start().and_then(p.many(1, 0)).must_end().compile().unwrap();
+        assert!(re.is_match("snake_case_42"));
+        assert!(!re.is_match("camelCase"));
+    }
+
+    #[test]
+    fn test_char_class() {
+        let p = char_class().range('a', 'c').chars(['x', 'y', 'z']);
+        assert_eq!("[a-cxyz]", p.to_string());
+        assert_eq!("char_class().range('a', 'c').chars(['x', 'y', 'z'])", p.to_code());
+        assert_eq!("[^a-cxyz]", any_except(p.clone()).to_string());
+        assert_eq!(
+            "any_except(char_class().range('a', 'c').chars(['x', 'y', 'z']))",
+            any_except(p.clone()).to_code()
+        );
+        assert_eq!(
+            r"[\-a-zA-Z_0-9]+",
+            one_of(['-']).range('a', 'z').range('A', 'Z').chars(['_']).range('0', '9').many(1, 0).to_string()
+        );
+        assert_eq!(
+            "char_class().chars(['-']).range('a', 'z').range('A', 'Z').chars(['_'])",
+            one_of(['-']).range('a', 'z').range('A', 'Z').chars(['_']).to_code()
+        );
+        assert_eq!("one_of(['a', 'b'])", char_class().chars(['a', 'b']).to_code());
+        let re = at_start().and_then(p.clone().many(1, 0)).must_end().compile().unwrap();
+        assert!(re.is_match("abzy"));
+        assert!(!re.is_match("abd"));
+        let re = any_except(p).compile().unwrap();
+        assert_eq!(Some("d"), re.find("abdx").map(|m| m.as_str()));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid range: 'z' is after 'a'")]
+    fn test_char_class_invalid_range() {
+        char_class().range('z', 'a');
+    }
+
+    #[test]
+    #[should_panic(expected = "not a character class: a")]
+    fn test_char_class_not_class() {
+        text("a").chars(['b']);
+    }
+
+    #[test]
+    fn test_any_except_sets() {
+        assert_eq!("[^aeiou]", any_except(one_of(['a', 'e', 'i', 'o', 'u'])).to_string());
+        assert_eq!("[^a-z]", any_except(char_range('a', 'z')).to_string());
+        let p = any_except(either((char_range('a', 'z'), char_range('0', '9'), one_of(['_', '-']))));
+        assert_eq!(r"[^a-z0-9_\-]", p.to_string());
+        assert_eq!(
+            "any_except(either((char_range('a', 'z'), char_range('0', '9'), one_of(['_', '-']))))",
+            p.to_code()
+        );
+        assert!(p.validate().is_empty());
+        let unsupported = any_except(either((char_range('a', 'z'), digit())));
+        assert!(matches!(unsupported.compile(), Err(regex::Error::Syntax(..))));
+        assert!(!unsupported.validate().is_empty());
+        let re = at_start().and_then(p.many(1, 0)).must_end().compile().unwrap();
+        assert!(re.is_match("ABC!"));
+        assert!(!re.is_match("AbC"));
+        assert!(!re.is_match("A_C"));
+        let re = any_except(one_of(['a', 'e', 'i', 'o', 'u'])).compile().unwrap();
+        assert_eq!(Some("x"), re.find("aexo").map(|m| m.as_str()));
+    }
+
+    #[test]
+    fn test_letter() {
+        assert_eq!(r"\pL", letter().to_string());
+        let re = regex::Regex::new(&at_start().and_then(letter()).must_end().to_string()).unwrap();
+        assert!(re.is_match("A"));
+        assert!(re.is_match("é"));
+        assert!(!re.is_match("5"));
+        assert!(any_except(letter()).compile().unwrap().is_match("5"));
+    }
+
+    #[test]
+    fn test_number() {
+        assert_eq!(r"\pN", number().to_string());
+        assert_eq!(r"\PN", any_except(number()).to_string());
+        assert_eq!("number().one_or_more()", number().many(1, 0).to_code());
+        let number = number().compile().unwrap();
+        let digit = digit().compile().unwrap();
+        for n in &["5", "\u{0663}", "½", "Ⅻ"] {
+            assert!(number.is_match(n));
+        }
+        assert!(digit.is_match("\u{0663}"));
+        assert!(!digit.is_match("½"));
+        assert!(!number.is_match("a"));
+    }
+
+    #[test]
+    fn test_unicode_category() {
+        assert_eq!(r"\p{Lu}", unicode_category("Lu").to_string());
+        assert_eq!(r"\P{Lu}", any_except(unicode_category("Lu")).to_string());
+        assert_eq!(r"\p{Lu}", any_except(any_except(unicode_category("Lu"))).to_string());
+        assert_eq!(r"\p{P}+", unicode_category("P").many(1, 0).to_string());
+        assert_eq!(
+            r#"unicode_category("Greek").and_then(any_except(unicode_category("Lu")))"#,
+            start_with(unicode_category("Greek"))
+                .and_then(any_except(unicode_category("Lu")))
+                .to_code()
+        );
+        let upper = unicode_category("Lu").compile().unwrap();
+        assert!(upper.is_match("É"));
+        assert!(!upper.is_match("é"));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown Unicode class: Klingon")]
+    fn test_unknown_unicode_category() {
+        unicode_category("Klingon");
+    }
+
+    #[test]
+    fn test_backref() {
+        assert_eq!(r"\1", backref(1).to_string());
+        assert_eq!(r"\k<year>", backref_name("year").to_string());
+        let p = start_with(one_of(['\'', '"']).named("quote"))
+            .and_then(word_character().many(0, 0).lazy())
+            .and_then(backref_name("quote"));
+        assert_eq!(r#"(?P<quote>['"])\w*?\k<quote>"#, p.to_string());
+        assert_eq!(
+            r#"start_with(one_of(['\'', '"']).named("quote")).and_maybe_many(word_character()).lazy().and_then(backref_name("quote"))"#,
+            p.to_code()
+        );
+        assert_eq!(
+            "start_with(digit().captured()).and_then(backref(1)).times(2)",
+            start_with(digit().captured()).and_then(backref(1).times(2)).to_code()
+        );
+        assert_eq!(r"(\d)\1{2}", start_with(digit().captured()).and_then(backref(1).times(2)).to_string());
+        assert_eq!(
+            r"backreferences are not supported: \k<quote>",
+            p.compile().unwrap_err().to_string()
+        );
+        assert!(backref(1).compile_bytes().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "backreference group index must start at 1")]
+    fn test_backref_zero() {
+        backref(0);
+    }
+
+    #[test]
+    fn test_hex_char() {
+        assert_eq!(r"\x41", hex_char(0x41).to_string());
+        assert_eq!(r"\x07", hex_char(7).to_string());
+        assert_eq!(r"\x{1F600}", hex_char(0x1F600).to_string());
+        assert_eq!(r"\x{100}", hex_char(0x100).to_string());
+        assert_eq!(r"[^\x{1F600}]", any_except(hex_char(0x1F600)).to_string());
+        assert_eq!("hex_char(0x41)", hex_char(0x41).to_code());
+        assert_eq!(
+            r#"start_with("a").and_then(hex_char(0x1F600))"#,
+            start_with("a").and_then(hex_char(0x1F600)).to_code()
+        );
+        assert!(hex_char(0x1F600).compile().unwrap().is_match("\u{1F600}"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid code point: 0xD800")]
+    fn test_invalid_hex_char() {
+        hex_char(0xD800);
+    }
+
+    #[test]
+    fn test_line_characters() {
+        assert_eq!(r"\t", tab().to_string());
+        assert_eq!(r"\n", newline().to_string());
+        assert_eq!(r"\r", carriage_return().to_string());
+        assert_eq!(r"\r?\n", maybe(carriage_return()).and_then(newline()).to_string());
+        assert_eq!(r"[^\n]", any_except(newline()).to_string());
+        assert_eq!(
+            "tab().and_then(carriage_return()).and_then(newline())",
+            start_with(tab()).and_then(carriage_return()).and_then(newline()).to_code()
+        );
+        assert!(tab().compile().unwrap().is_match("a\tb"));
+    }
+
+    #[test]
+    fn test_password() {
+        let p = at_start()
+            .and_then(followed_by(anything().many(0, 0).and_then(digit())))
+            .and_then(followed_by(anything().many(0, 0).and_then(char_range('a', 'z'))))
+            .and_then(anything().repeat(Repeat::AtLeast(8)))
+            .must_end();
+        assert_eq!(r"^(?=.*\d)(?=.*[a-z]).{8,}$", p.to_string());
+    }
+
+    #[test]
+    fn test_lookbehind() {
+        let p = preceded_by("$").and_then(digit().many(1, 0));
+        assert_eq!(r"(?<=\$)\d+", p.to_string());
+        assert_eq!(r#"preceded_by("$").and_many(digit())"#, p.to_code());
+        let p = start_with(not_preceded_by("-")).and_then(digit());
+        assert_eq!(r"(?<!-)\d", p.to_string());
+        assert_eq!(r#"not_preceded_by("-").and_then(digit())"#, p.to_code());
+        assert!(matches!(p.compile(), Err(regex::Error::Syntax(..))));
+    }
+
+    #[test]
+    fn test_capture() {
+        let p = start_with("x").and_then(capture(digit().times(4)));
+        assert_eq!(r"x(\d{4})", p.to_string());
+        assert_eq!(r#"start_with("x").and_then(digit().times(4).captured())"#, p.to_code());
+        assert_eq!(p, start_with("x").and_then(digit().times(4)).captured());
+        let caps = p.compile().unwrap().captures("x2021").unwrap();
+        assert_eq!("2021", &caps[1]);
+    }
+
+    #[test]
+    fn test_group() {
+        let p = group(text("ab")).and_maybe_many("c");
+        assert_eq!("(?:ab)c*", p.to_string());
+        assert_eq!(r#"group("ab").and_maybe_many("c")"#, p.to_code());
+        let p = start_with("x").and_then(group("ab").many(1, 0));
+        assert_eq!("x(?:ab)+", p.to_string());
+        assert_eq!(r#"start_with("x").and_many(group("ab"))"#, p.to_code());
+        assert_eq!(0, p.compile().unwrap().captures_len() - 1);
+    }
+
+    #[test]
+    fn test_whitespace() {
+        assert_eq!(r"\s", whitespace().to_string());
+        assert_eq!(r"\S", any_except(whitespace()).to_string());
+        let p = start_with(word_character().many(1, 0))
+            .and_many(whitespace())
+            .and_then(any_except(whitespace()));
+        assert_eq!(r"\w+\s+\S", p.to_string());
+        assert_eq!(
+            "start_with(word_character().one_or_more()).and_many(whitespace()).and_then(any_except(whitespace()))",
+            p.to_code()
+        );
+        assert_eq!("whitespace().times(2)", whitespace().times(2).to_code());
+    }
+
+    #[test]
+    fn test_text_anchors() {
+        let p = at_text_start().and_then(digit()).must_end_text();
+        assert_eq!(r"\A\d\z", p.to_string());
+        assert_eq!("at_text_start().and_then(digit()).must_end_text()", p.to_code());
+        let lines = at_start().and_then(digit()).must_end();
+        assert_eq!("at_start().and_then(digit()).must_end()", lines.to_code());
+        let text = regex::RegexBuilder::new(&p.to_string()).multi_line(true).build().unwrap();
+        let line = regex::RegexBuilder::new(&lines.to_string())
+            .multi_line(true)
+            .build()
+            .unwrap();
+        assert!(line.is_match("a\n1\nb"));
+        assert!(!text.is_match("a\n1\nb"));
+        assert!(text.is_match("1"));
+    }
+
+    #[test]
+    fn test_word_boundary() {
+        let p = word_boundary()
+            .and_then(word_character().many(1, 0))
+            .and_then(word_boundary());
+        assert_eq!(r"\b\w+\b", p.to_string());
+        assert_eq!(
+            "word_boundary().and_many(word_character()).and_then(word_boundary())",
+            p.to_code()
+        );
+        let p = start_with("a").and_then(not_word_boundary()).and_then("b");
+        assert_eq!(r"a\Bb", p.to_string());
+        assert_eq!(r#"start_with("a").and_then(not_word_boundary()).and_then("b")"#, p.to_code());
+        let p = word_boundary().and_then("cat").and_then(word_boundary());
+        let re = regex::Regex::new(&p.to_string()).unwrap();
+        assert!(re.is_match("a cat here"));
+        assert!(!re.is_match("concatenate"));
+    }
+
+    #[test]
+    fn test_tuple_pattern_list() {
+        assert_eq!("a|b|c|d", either(("a", "b", "c", "d")).to_string());
+        assert_eq!(r"a|b|\d|c|d", either(("a", "b", digit(), "c", "d")).to_string());
+        let p = start_with("x").and_either(("a", "b", "c", "d", "e", digit().times(2)));
+        assert_eq!(r"x(a|b|c|d|e|\d{2})", p.to_string());
+    }
+
+    #[test]
+    fn test_or_else() {
+        let p = either(("a", "b")).or_else("c");
+        assert_eq!("a|b|c", p.to_string());
+        assert_eq!(p, either(("a", "b", "c")));
+        assert_eq!("a|b", text("a").or_else("b").to_string());
+        let p = either(("a", "b", "c", "d", "e", "f")).or_else("g").or_else(digit());
+        assert_eq!(r"a|b|c|d|e|f|g|\d", p.to_string());
+        assert_eq!(
+            r#"either(("a", "b", "c", "d", "e", "f")).or_else("g").or_else(digit())"#,
+            p.to_code()
+        );
+        let p = start_with("x").and_then(p);
+        assert_eq!(
+            r#"start_with("x").and_then(either(("a", "b", "c", "d", "e", "f")).or_else("g").or_else(digit()))"#,
+            p.to_code()
+        );
+        assert_eq!(r"x(a|b|c|d|e|f|g|\d)", p.to_string());
+    }
+
+    #[test]
+    fn test_array_pattern_list() {
+        assert_eq!("gray|grey|greye", either(["gray", "grey", "greye"]).to_string());
+        let p = start_with("x").and_either([digit(), letter().times(2)]);
+        assert_eq!(r"x(\d|\pL{2})", p.to_string());
+        let word = String::from("b");
+        assert_eq!("a|b", either([String::from("a"), word]).to_string());
+    }
+
+    #[test]
+    fn test_text_escape() {
+        let p = text("a.b+c");
+        assert_eq!(r"a\.b\+c", p.to_string());
+        assert_eq!(
+            r"\.\+\*\?\(\)\[\]\{\}\^\$\|\\-#",
+            text(r".+*?()[]{}^$|\-#").to_string()
+        );
+        assert_eq!(r#"text("a\\b")"#, text(r"a\b").to_code());
+        for t in &["a.b+c", "(1+1)*2=4?", r"C:\dir\[x]", "$^|{}"] {
+            let re = regex::Regex::new(&format!("^{}$", text(t))).unwrap();
+            assert!(re.is_match(t));
+        }
+        assert!(!regex::Regex::new(&text("a.b+c").to_string())
+            .unwrap()
+            .is_match("axbbc"));
+        assert_eq!(r"(?:\.\.)+", text("..").many(1, 0).to_string_uncaptured());
+    }
+
+    #[test]
+    fn test_quantified_alternation() {
+        let p = either(("a", "b")).many(2, 4);
+        assert_eq!("(?:a|b){2,4}", p.to_string());
+        assert_eq!(r#"either(("a", "b")).many(2, 4)"#, p.to_code());
+        assert_eq!(0, p.count_capture_groups());
+        assert_eq!(
+            "x(?:a|b)+",
+            start_with("x").and_many(either(("a", "b"))).to_string()
+        );
+        let p2 = either(("a", "b")).times(2);
+        assert_eq!("(?:a|b){2}", p2.to_string());
+        assert_eq!(r#"either(("a", "b")).times(2)"#, p2.to_code());
+        assert_eq!(p2, crate::parser::explain(&p2.to_string()).unwrap());
+        let re = regex::Regex::new(&format!("^{}$", p)).unwrap();
+        assert!(re.is_match("abba"));
+        assert!(!re.is_match("a"));
+        assert!(!re.is_match("ababa"));
+    }
+
+    #[test]
+    fn test_to_module() {
+        let p = crate::explain(r"^(?P<y>\d{4})-(?P<m>\d{2})$").unwrap();
+        let module = p.to_module("date");
+        assert!(module.starts_with("use ezregexp::*;\n"));
+        assert!(module.contains("pub fn date() -> Pattern {\n"));
+        assert!(module.contains(&format!("    {}\n}}", p.to_code())));
+        assert!(module.contains(r#"digit().times(4).named("y")"#));
+    }
+
+    #[test]
+    fn test_to_module_compiles() {
+        use crate::curated::{anything_including_newline, text_ci, uuid};
+        let p = start_with(at_start())
+            .and_then(anything())
+            .and_then(digit().times(2))
+            .and_then(letter().at_least(2))
+            .and_then(number().at_most(3))
+            .and_then(word_character().many(2, 4).lazy())
+            .and_then(maybe(whitespace()))
+            .and_then(tab())
+            .and_then(newline().one_or_more())
+            .and_then(carriage_return().zero_or_more())
+            .and_then(control_char('A'))
+            .and_then(hex_char(0xFEFF))
+            .and_then(word_boundary())
+            .and_then(not_word_boundary())
+            .and_then(posix_class("alpha"))
+            .and_then(unicode_category("Greek"))
+            .and_then(any_except(unicode_category("Greek")))
+            .and_then(any_except(digit()))
+            .and_then(char_range('a', 'z'))
+            .and_then(one_of(['x', 'y']))
+            .and_then(any_except(char_class().range('a', 'c').chars(['_'])))
+            .and_then(either(("cat", "dog")))
+            .and_then(raw(r"\d"))
+            .and_then(group(text("ab")))
+            .and_then(text("cd").captured())
+            .and_then(letter().named("l"))
+            .and_then(text("e").case_insensitive().without_flags("s"))
+            .and_then(text("f").multiline().dotall().flags("U"))
+            .and_then(followed_by("g"))
+            .and_then(not_followed_by("h"))
+            .and_then(preceded_by("i"))
+            .and_then(not_preceded_by("j"))
+            .and_then(backref(1))
+            .and_then(backref_name("l"))
+            .and_then(text_ci("k"))
+            .and_then(anything_including_newline())
+            .and_then(uuid())
+            .and_then(at_text_start())
+            .and_maybe("m")
+            .and_maybe_many("n")
+            .and_many("o")
+            .must_end();
+        // the generated module is built as the `generated_module` example
+        assert_eq!(
+            include_str!("../examples/generated_module/everything.rs"),
+            p.to_module("everything")
+        );
+    }
+
+    #[test]
+    fn test_uncaptured() {
+        let p = start_with(digit().times(4).named("y"))
+            .and_then("-")
+            .and_either(("a", "b"))
+            .and_then(text("cd").times(2));
+        assert_eq!(r"(?P<y>\d{4})-(a|b)(cd){2}", p.to_string());
+        let s = p.to_string_uncaptured();
+        assert_eq!(r"(?:\d{4})-(?:a|b)(?:cd){2}", s);
+        assert!(!s.contains("(?P<"));
+        assert!(s.match_indices('(').all(|(i, _)| s[i + 1..].starts_with('?')));
+    }
+
+    #[test]
+    fn test_to_verbose() {
+        let p = start_with(digit().times(4).named("year"))
+            .and_then("-")
+            .and_then(digit().times(2).named("month"))
+            .and_then("-")
+            .and_then(digit().times(2).named("day"));
+        let verbose = p.to_verbose();
+        assert_eq!(
+            "(?x)\n(?P<year>\\d{4})  # capture: year\n-\n(?P<month>\\d{2})  # capture: month\n-\n(?P<day>\\d{2})  # capture: day\n",
+            verbose
+        );
+        for name in &["year", "month", "day"] {
+            assert!(verbose.contains(&format!("# capture: {}", name)));
+        }
+        let caps = regex::Regex::new(&verbose).unwrap().captures("2010-03-14").unwrap();
+        assert_eq!("03", &caps["month"]);
+
+        let p = start_with("a #").and_either(("b", "c")).and_then(" d");
+        assert_eq!("(?x)\na\\ \\#\n(b|c)\n\\ d\n", p.to_verbose());
+        assert!(regex::Regex::new(&p.to_verbose()).unwrap().is_match("a #c d"));
+        let p = start_with("a").and_then(one_of([' ', ','])).and_then("b");
+        assert_eq!("(?x)\na\n[\\ ,]\nb\n", p.to_verbose());
+        let classes = [
+            p,
+            start_with(char_range(' ', '#')).and_then(any_except(one_of([' ', '\u{A0}']))),
+            start_with(either((char_range('a', 'b'), one_of([' ']))))
+                .and_then(any_except(one_of(['\t', ' ']))),
+        ];
+        for p in &classes {
+            let re = regex::Regex::new(&p.to_regex_string()).unwrap();
+            let verbose = regex::Regex::new(&p.to_verbose()).unwrap();
+            for input in &["a b", "a,b", "ab", " x", "#\u{A0}", "!y", "a\t", " z", "b "] {
+                assert_eq!(re.is_match(input), verbose.is_match(input), "{} on {:?}", p, input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_string_escaping_slashes() {
+        let p = start_with("https://").and_then(word_character().many(1, 0)).and_then("/");
+        assert_eq!(r"https://\w+/", p.to_string());
+        assert_eq!(r"https:\/\/\w+\/", p.to_string_escaping_slashes());
+        assert_eq!(r"[\/-9]", char_range('/', '9').to_string_escaping_slashes());
+        assert_eq!(r"\d+", digit().many(1, 0).to_string_escaping_slashes());
+    }
+
+    #[test]
+    fn test_to_string_deterministic() {
+        let p1 = start_with("gr").and_either(("e", "a")).and_then("y");
+        let p2 = start_with("gr").and_either(("a", "e")).and_then("y");
+        assert_ne!(p1.to_string(), p2.to_string());
+        assert_eq!("gr(a|e)y", p1.to_string_deterministic());
+        assert_eq!(p1.to_string_deterministic(), p2.to_string_deterministic());
+        let nested = either((either(("z", "y")).named("n"), "x"));
+        assert_eq!("(?P<n>y|z)|x", nested.to_string_deterministic());
+    }
+
+    #[test]
+    fn test_lazy() {
+        let p = digit().and_many(digit()).lazy();
+        assert_eq!(r"\d\d+?", p.to_string());
+        assert_eq!("digit().and_many(digit()).lazy()", p.to_code());
+        assert_eq!("maybe(\"a\").lazy()", maybe("a").lazy().to_code());
+        let p = start_with("<").and_then(word_character().many(0, 0).lazy()).and_then(">");
+        assert_eq!(r"<\w*?>", p.to_string());
+        assert_eq!(
+            r#"start_with("<").and_maybe_many(word_character()).lazy().and_then(">")"#,
+            p.to_code()
+        );
+        let re = regex::Regex::new(&p.to_string()).unwrap();
+        assert_eq!("<a>", re.find("<a><b>").unwrap().as_str());
+    }
+
+    #[test]
+    fn test_both_greediness() {
+        assert_eq!(
+            (r"\d+".to_owned(), r"\d+?".to_owned()),
+            digit().many(1, 0).both_greediness()
+        );
+        assert_eq!(
+            ("a.*".to_owned(), "a.*?".to_owned()),
+            start_with("a").and_maybe_many(anything()).both_greediness()
+        );
+        assert_eq!(
+            ("ab".to_owned(), "ab".to_owned()),
+            text("ab").both_greediness()
+        );
+    }
+
+    #[test]
+    fn test_raw_escape() {
+        assert_eq!(r"a\.b\+c", escape("a.b+c"));
+        let p = raw(r"\d+").and_then(raw(&escape("1.5")));
+        assert_eq!(r"\d+1\.5", p.to_string());
+        assert_eq!(r#"raw("\\d+").and_then(raw("1\\.5"))"#, p.to_code());
+    }
+
+    #[test]
+    fn test_builder_config() {
+        let p = digit().times(4).and_then(any_except(word_character()));
+        assert_eq!(r"\d{4}\W", p.to_string());
+        BuilderConfig { ascii: true }.set();
+        assert_eq!("[0-9]", digit().to_string());
+        assert_eq!("[0-9]{4}[^0-9A-Za-z_]", p.to_string());
+        assert_eq!("[a-zA-Z]", letter().to_string());
+        let ws = regex::Regex::new(&any_except(whitespace()).to_string()).unwrap();
+        assert!(ws.is_match("\u{A0}"));
+        assert!(!ws.is_match(" \t\n"));
+        BuilderConfig::default().set();
+        assert_eq!(r"\d", digit().to_string());
+    }
+
+    #[test]
+    fn test_replace_named() {
+        let skeleton = start_with("key=")
+            .and_then(anything().named("value"))
+            .and_then(";");
+        assert_eq!("key=(?P<value>.);", skeleton.to_string());
+        let p = skeleton.replace_named("value", digit().many(1, 0));
+        assert_eq!(r"key=(?P<value>\d+);", p.to_string());
+        assert_eq!(
+            r#"start_with("key=").and_then(digit().one_or_more().named("value")).and_then(";")"#,
+            p.to_code()
+        );
+        let p = p.replace_named("other", letter());
+        assert_eq!(r"key=(?P<value>\d+);", p.to_string());
+    }
+
+    #[test]
+    fn test_posix_class() {
+        let p = start_with(posix_class("alpha")).and_then(any_except(posix_class("digit")));
+        assert_eq!("[[:alpha:]][[:^digit:]]", p.to_string());
+        assert_eq!(
+            r#"posix_class("alpha").and_then(any_except(posix_class("digit")))"#,
+            p.to_code()
+        );
+        assert_eq!("[[:space:]]+", posix_class("space").many(1, 0).to_string());
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let p = text("abc").case_insensitive();
+        assert_eq!("(?i:abc)", p.to_string());
+        assert_eq!(r#"text("abc").case_insensitive()"#, p.to_code());
+        assert_eq!("(?i:abc)", p.case_insensitive().to_string());
+        let p = start_with("id=").and_then(digit().and_then("x").case_insensitive());
+        assert_eq!(r"id=(?i:\dx)", p.to_string());
+        assert_eq!(
+            r#"start_with("id=").and_then(digit().and_then("x").case_insensitive())"#,
+            p.to_code()
+        );
+        let p = text("abc").case_insensitive();
+        assert!(p.compile().unwrap().is_match("ABC"));
+    }
+
+    #[test]
+    fn test_flags() {
+        let p = start_with(at_start()).and_then("a").flags("mi");
+        assert_eq!("(?mi:^a)", p.to_string());
+        assert_eq!(r#"at_start().and_then("a").multiline().case_insensitive()"#, p.to_code());
+        assert_eq!("(?mis:^a)", p.flags("si").to_string());
+        let re = text("a").must_end().flags("m").compile().unwrap();
+        assert!(re.is_match("a\nb"));
+    }
+
+    #[test]
+    fn test_without_flags() {
+        assert_eq!("(?-i:a)", text("a").without_flags("i").to_string());
+        let p = text("a").case_insensitive().without_flags("s");
+        assert_eq!("(?i-s:a)", p.to_string());
+        assert_eq!(r#"text("a").case_insensitive().without_flags("s")"#, p.to_code());
+        assert_eq!("(?i-s:a)", p.clone().multiline().to_string().replace('m', ""));
+        assert_eq!("(?-i:a)", text("a").without_flags("i").case_insensitive().to_string());
+        assert_eq!("(?i:a)", text("a").case_insensitive().without_flags("i").to_string());
+        assert!(text("a").without_flags("i").compile().unwrap().is_match("a"));
+    }
+
+    #[test]
+    fn test_multiline_dotall() {
+        let p = anything().dotall();
+        assert_eq!("(?s:.)", p.to_string());
+        assert!(p.compile().unwrap().is_match("\n"));
+        assert!(!anything().compile().unwrap().is_match("\n"));
+        let p = at_start().and_then(anything().many(1, 0)).must_end().multiline().dotall();
+        assert_eq!("(?ms:^.+$)", p.to_string());
+        assert_eq!(p, p.clone().dotall().multiline());
+        assert!(p.compile().unwrap().is_match("a\nb"));
+        let p = at_start().and_then(digit()).must_end().multiline().dotall();
+        assert_eq!("at_start().and_then(digit()).must_end().multiline().dotall()", p.to_code());
+    }
+
+    #[test]
+    fn test_verbose_flag() {
+        let p = text("a b").flags("x");
+        assert_eq!(r"(?x:a\ b)", p.to_string());
+        assert!(p.compile().unwrap().is_match("a b"));
+        let p = text("a#b").flags("x");
+        assert_eq!(r"(?x:a\#b)", p.to_string());
+        assert!(p.compile().unwrap().is_match("a#b"));
+        let p = start_with(text("a b").without_flags("x")).and_then(" c").flags("x");
+        assert_eq!(r"(?x:(?-x:a b)\ c)", p.to_string());
+        assert!(p.compile().unwrap().is_match("a b c"));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown flag")]
+    fn test_unknown_flag() {
+        text("a").flags("q");
+    }
+
+    #[test]
+    fn test_char_range() {
+        assert_eq!("[a-z]", char_range('a', 'z').to_string());
+        assert_eq!("[0-9]+", char_range('0', '9').many(1, 0).to_string());
+        assert_eq!(r"[\--\]]", char_range('-', ']').to_string());
+        let p = start_with(char_range('a', 'f')).and_then(char_range('0', '9').times(2));
+        assert_eq!("[a-f][0-9]{2}", p.to_string());
+        assert_eq!("char_range('a', 'f').and_then(char_range('0', '9')).times(2)", p.to_code());
+    }
+
+    #[test]
+    fn test_try_char_range() {
+        assert_eq!(Ok(char_range('a', 'z')), try_char_range('a', 'z'));
+        assert_eq!(Ok("[0-9]".to_owned()), try_char_range('0', '9').map(|p| p.to_string()));
+        let err = try_char_range('z', 'a').unwrap_err();
+        assert_eq!(InvalidRange { from: 'z', to: 'a' }, err);
+        assert_eq!("invalid range: 'z' is after 'a'", err.to_string());
+        assert_eq!("[^a-z]", any_except(char_range('a', 'z')).to_string());
+        assert_eq!(
+            "any_except(char_range('a', 'z'))",
+            any_except(char_range('a', 'z')).to_code()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid range")]
+    fn test_char_range_panics() {
+        char_range('9', '0');
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let p = start_with(digit().times(4).named("year"))
+            .and_then("-")
+            .and_then(digit().many(1, 2).named("month"))
+            .and_then(either(("-", "/")))
+            .and_then(digit().times(2).named("day"));
+        let json = serde_json::to_string(&p).unwrap();
+        assert!(json.contains(r#"{"Many":{"exp":"Digit","low":4,"high":4,"lazy":false}}"#));
+        assert!(json.contains(r#""name":"year""#));
+        let back: Pattern = serde_json::from_str(&json).unwrap();
+        assert_eq!(p, back);
+        assert_eq!(p.to_regex_string(), back.to_regex_string());
+    }
+
+    #[test]
+    fn test_to_regex_string() {
+        let p = start_with(digit().times(4).named("year"))
+            .and_then("-")
+            .and_maybe(either(("a", "b")))
+            .must_end();
+        assert_eq!(r"(?P<year>\d{4})-(?:a|b)?$", p.to_regex_string());
+        assert_eq!(p.to_string(), p.to_regex_string());
+        BuilderConfig { ascii: true }.set();
+        let regex = digit().to_regex_string();
+        BuilderConfig::default().set();
+        assert_eq!("[0-9]", regex);
+    }
+
+    #[test]
+    fn test_optional() {
+        let p = start_with("colo").and_then("u").optional().and_then("r");
+        assert_eq!("colou?r", p.to_string());
+        assert_eq!(r#"start_with("colo").and_maybe("u").and_then("r")"#, p.to_code());
+        assert_eq!(start_with("colo").and_maybe("u").and_then("r"), p);
+        assert_eq!(r"\d?", digit().optional().to_string());
+        assert_eq!("(ab)?", text("ab").optional().to_string());
+        assert_eq!(maybe(digit()), digit().optional());
+        let re = at_start().and_then(p).must_end().compile().unwrap();
+        assert!(re.is_match("color"));
+        assert!(re.is_match("colour"));
+        assert!(!re.is_match("colouur"));
+    }
+
+    #[test]
+    fn test_one_or_more_zero_or_more() {
+        assert_eq!(r"\d+", digit().one_or_more().to_string());
+        assert_eq!(r"\d*", digit().zero_or_more().to_string());
+        assert_eq!("digit().one_or_more()", digit().one_or_more().to_code());
+        assert_eq!("digit().zero_or_more()", digit().zero_or_more().to_code());
+        let p = start_with("a").and_then(digit()).one_or_more().and_then("b").zero_or_more();
+        assert_eq!(r"a\d+b*", p.to_string());
+        assert_eq!(start_with("a").and_then(digit().many(1, 0)).and_then(text("b").many(0, 0)), p);
+        assert_eq!(
+            r#"start_with("a").and_many(digit()).and_maybe_many("b")"#,
+            p.to_code()
+        );
+        assert_eq!("(ab)+", text("ab").one_or_more().to_string());
+        assert_eq!(
+            r#"text("ab").one_or_more().lazy()"#,
+            text("ab").one_or_more().lazy().to_code()
+        );
+        assert_eq!(
+            "start_with(digit().one_or_more()).and_then(letter())",
+            start_with(digit().one_or_more()).and_then(letter()).to_code()
+        );
+    }
+
+    #[test]
+    fn test_at_least_at_most() {
+        assert_eq!(r"\d{3,}", digit().at_least(3).to_string());
+        assert_eq!(r"\d{0,2}", digit().at_most(2).to_string());
+        assert_eq!("digit().at_least(3)", digit().at_least(3).to_code());
+        assert_eq!("digit().at_most(2)", digit().at_most(2).to_code());
+        let p = start_with("a").and_then(digit()).at_most(2);
+        assert_eq!(r"a\d{0,2}", p.to_string());
+        assert_eq!(r#"start_with("a").and_then(digit()).at_most(2)"#, p.to_code());
+        assert_eq!("digit().zero_or_more()", digit().many(0, 0).to_code());
+    }
+
+    #[test]
+    #[should_panic(expected = "at_most needs a bound of at least 1")]
+    fn test_at_most_zero() {
+        digit().at_most(0);
+    }
+
+    #[test]
+    fn test_separated_by() {
+        let p = digit().separated_by(",");
+        assert_eq!(r"\d(?:,\d)*", p.to_string());
+        assert_eq!(r#"digit().separated_by(",")"#, p.to_code());
+        let re = at_start().and_then(p).must_end().compile().unwrap();
+        assert!(re.is_match("1,2,3"));
+        assert!(re.is_match("1"));
+        assert!(!re.is_match("1,2,"));
+        assert!(!re.is_match("12"));
+        let p = start_with("[")
+            .and_then(text("ab").or_else(digit().many(1, 0)))
+            .separated_by(", ")
+            .and_then("]");
+        assert_eq!(r"\[(ab|\d+)(?:, (ab|\d+))*\]", p.to_string());
+        assert_eq!(
+            r#"start_with("[").and_then(either(("ab", digit().one_or_more())).separated_by(", ")).and_then("]")"#,
+            p.to_code()
+        );
+        assert_eq!(
+            r#"text("ab").separated_by(one_of([',', ';']))"#,
+            text("ab").separated_by(one_of([',', ';'])).to_code()
+        );
+    }
+
+    #[test]
+    fn test_quantifier_grouping() {
+        assert_eq!("(ab){2}", text("ab").times(2).to_string());
+        assert_eq!(r"\d{2}", digit().times(2).to_string());
+        assert_eq!("[ab]{2}", one_of(['a', 'b']).times(2).to_string());
+        assert_eq!(r"\.{2}", text(".").times(2).to_string());
+        assert_eq!(r"(\.\+){2}", text(".+").times(2).to_string());
+        assert_eq!("é{2}", text("é").times(2).to_string());
+        assert_eq!(".*", anything().many(0, 0).to_string());
+        assert_eq!(r"[\s\S]*", raw(r"[\s\S]").many(0, 0).to_string());
+        assert_eq!(r"\x{FEFF}?", raw(r"\x{FEFF}").many(0, 1).to_string());
+        assert_eq!(r"(\s+)?", raw(r"\s+").many(0, 1).to_string());
+        assert_eq!("(?:ab)+", group("ab").many(1, 0).to_string());
+        assert_eq!(r"(a\d)?", maybe(start_with("a").and_then(digit())).to_string());
+    }
+
+    #[test]
+    fn test_repeat() {
+        assert_eq!(r"\d*", digit().repeat(Repeat::ZeroOrMore).to_string());
+        assert_eq!(r"\d+", digit().repeat(Repeat::OneOrMore).to_string());
+        assert_eq!(r"\d?", digit().repeat(Repeat::Optional).to_string());
+        assert_eq!(r"\d{3}", digit().repeat(Repeat::Exactly(3)).to_string());
+        assert_eq!(r"\d{2,4}", digit().repeat(Repeat::Between(2, 4)).to_string());
+        assert_eq!(r"\d{3,}", digit().repeat(Repeat::AtLeast(3)).to_string());
+        assert_eq!(
+            "digit().at_least(3)",
+            digit().repeat(Repeat::AtLeast(3)).to_code()
+        );
+        assert_eq!(
+            r#"start_with("a").and_then(digit()).at_least(3)"#,
+            start_with("a").and_then(digit()).repeat(Repeat::AtLeast(3)).to_code()
+        );
+        assert_eq!(r"\d{0,2}", digit().repeat(Repeat::Between(0, 2)).to_string());
+        assert_eq!(r"\d{2}", digit().repeat(Repeat::Between(2, 2)).to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "repetition needs a bound of at least 1")]
+    fn test_repeat_exactly_zero() {
+        digit().repeat(Repeat::Exactly(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "repetition needs an upper bound of at least 1")]
+    fn test_repeat_between_zero() {
+        digit().repeat(Repeat::Between(0, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid repetition bounds: 2 is greater than 1")]
+    fn test_repeat_inverted_bounds() {
+        digit().repeat(Repeat::Between(2, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "repetition needs an upper bound of at least 1")]
+    fn test_repeat_unbounded_between() {
+        digit().repeat(Repeat::Between(2, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "repetition needs a bound of at least 1")]
+    fn test_times_zero() {
+        digit().times(0);
     }
 }
@@ -0,0 +1,1108 @@
+//! Curated helpers for common patterns
+
+use crate::builder::{
+    class_escape, separated, Pattern, ASCII_DIGIT, ASCII_LETTER, ASCII_WORD_CHARACTER,
+};
+
+/// Wrap the expansion of a helper, so code generation emits the helper call
+fn curated<S: Into<String>>(exp: Pattern, code: S) -> Pattern {
+    Pattern::Curated {
+        exp: Box::new(exp),
+        code: code.into(),
+    }
+}
+
+/// Start matching with the given pattern, anchored at the start of input: `^exp`.
+/// Unlike `start_with`, the pattern can't match further in the input
+pub fn starts_with_anchored<T: Into<Pattern>>(exp: T) -> Pattern {
+    let exp = exp.into();
+    let code = format!("starts_with_anchored({})", exp.to_argument_code());
+    curated(Pattern::Sequence(vec![Pattern::InputStart, exp]), code)
+}
+
+/// Match a whole line: `^.*$`.
+/// The anchors only match at line boundaries when the regex is compiled in
+/// multi-line mode (`(?m)` or `RegexBuilder::multi_line`), otherwise they match
+/// the start and end of the whole input
+pub fn whole_line() -> Pattern {
+    curated(
+        Pattern::Sequence(vec![
+            Pattern::InputStart,
+            any_count(),
+            Pattern::InputEnd,
+        ]),
+        "whole_line()",
+    )
+}
+
+/// Match a whole line containing the given pattern: `^.*exp.*$`.
+/// See `whole_line` for the multi-line behavior of the anchors
+pub fn whole_line_containing<T: Into<Pattern>>(exp: T) -> Pattern {
+    let exp = exp.into();
+    let code = format!("whole_line_containing({})", exp.to_argument_code());
+    curated(
+        Pattern::Sequence(vec![
+            Pattern::InputStart,
+            any_count(),
+            exp,
+            any_count(),
+            Pattern::InputEnd,
+        ]),
+        code,
+    )
+}
+
+/// Match any character, including a new line: `[\s\S]`.
+/// Unlike `anything`, this does not depend on the regex being compiled with the `s` flag
+pub fn anything_including_newline() -> Pattern {
+    curated(
+        Pattern::Raw(r"[\s\S]".to_owned()),
+        "anything_including_newline()",
+    )
+}
+
+/// Match the given text ignoring case, with a scoped flag: `(?i:text)`.
+/// The regex engine applies Unicode simple case folding, so `text_ci("k")` also matches
+/// the Kelvin sign `K`, which a `[kK]` class would miss. Locale specific mappings,
+/// like the Turkish dotted `İ`, are not applied
+pub fn text_ci(text: &str) -> Pattern {
+    curated(
+        Pattern::Text(text.to_owned()).case_insensitive(),
+        format!("text_ci({:?})", text),
+    )
+}
+
+/// Match the byte order mark `U+FEFF`, found at the start of some UTF-8 files: `\x{FEFF}`
+pub fn byte_order_mark() -> Pattern {
+    curated(Pattern::Raw(r"\x{FEFF}".to_owned()), "byte_order_mark()")
+}
+
+/// Match the given pattern, preceded by an optional byte order mark: `\x{FEFF}?exp`
+pub fn with_optional_bom<T: Into<Pattern>>(exp: T) -> Pattern {
+    let exp = exp.into();
+    let code = format!("with_optional_bom({})", exp.to_argument_code());
+    curated(
+        Pattern::Sequence(vec![Pattern::Raw(r"\x{FEFF}?".to_owned()), exp]),
+        code,
+    )
+}
+
+/// Unicode blocks, with their first and last code points
+const UNICODE_BLOCKS: [(&str, u32, u32); 18] = [
+    ("Basic_Latin", 0x0000, 0x007F),
+    ("Latin_1_Supplement", 0x0080, 0x00FF),
+    ("Latin_Extended_A", 0x0100, 0x017F),
+    ("Greek_and_Coptic", 0x0370, 0x03FF),
+    ("Greek", 0x0370, 0x03FF),
+    ("Cyrillic", 0x0400, 0x04FF),
+    ("Hebrew", 0x0590, 0x05FF),
+    ("Arabic", 0x0600, 0x06FF),
+    ("Devanagari", 0x0900, 0x097F),
+    ("Thai", 0x0E00, 0x0E7F),
+    ("General_Punctuation", 0x2000, 0x206F),
+    ("Box_Drawing", 0x2500, 0x257F),
+    ("Hiragana", 0x3040, 0x309F),
+    ("Katakana", 0x30A0, 0x30FF),
+    ("CJK_Unified_Ideographs", 0x4E00, 0x9FFF),
+    ("Hangul_Syllables", 0xAC00, 0xD7AF),
+    ("Emoticons", 0x1F600, 0x1F64F),
+    ("Miscellaneous_Symbols_and_Pictographs", 0x1F300, 0x1F5FF),
+];
+
+/// Match a character in the given Unicode block, like `CJK_Unified_Ideographs`.
+/// The `regex` crate has no block properties, so known blocks are rendered as their
+/// code point range, like `[\x{4E00}-\x{9FFF}]`. Case, spaces, hyphens and underscores
+/// in the name are ignored. Other names are rendered as `\p{name}`, which the `regex`
+/// crate only accepts for scripts and general categories
+pub fn unicode_block(name: &str) -> Pattern {
+    let key = block_key(name);
+    let exp = match UNICODE_BLOCKS.iter().find(|(n, _, _)| block_key(n) == key) {
+        Some((_, from, to)) => format!(r"[\x{{{:04X}}}-\x{{{:04X}}}]", from, to),
+        None => format!(r"\p{{{}}}", name),
+    };
+    curated(Pattern::Raw(exp), format!("unicode_block({:?})", name))
+}
+
+/// Block name used for lookups
+fn block_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '_' | ' ' | '-'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Match an ASCII digit: `[0-9]`, whatever the `BuilderConfig`
+pub fn ascii_digit() -> Pattern {
+    curated(Pattern::Raw(ASCII_DIGIT.to_owned()), "ascii_digit()")
+}
+
+/// Match an ASCII letter: `[a-zA-Z]`, whatever the `BuilderConfig`
+pub fn ascii_letter() -> Pattern {
+    curated(Pattern::Raw(ASCII_LETTER.to_owned()), "ascii_letter()")
+}
+
+/// Match an ASCII word character: `[0-9A-Za-z_]`, whatever the `BuilderConfig`
+pub fn ascii_word_character() -> Pattern {
+    curated(
+        Pattern::Raw(ASCII_WORD_CHARACTER.to_owned()),
+        "ascii_word_character()",
+    )
+}
+
+/// Match a hexadecimal number literal, as found in source code: `0[xX][0-9A-Fa-f]+`
+pub fn hex_number() -> Pattern {
+    curated(Pattern::Raw("0[xX][0-9A-Fa-f]+".to_owned()), "hex_number()")
+}
+
+/// Match a binary number literal, as found in source code: `0[bB][01]+`
+pub fn binary_number() -> Pattern {
+    curated(Pattern::Raw("0[bB][01]+".to_owned()), "binary_number()")
+}
+
+/// Match a MAC address: six hexadecimal pairs separated by `:` or `-`
+pub fn mac_address() -> Pattern {
+    curated(
+        Pattern::Raw("([0-9A-Fa-f]{2}[:-]){5}[0-9A-Fa-f]{2}".to_owned()),
+        "mac_address()",
+    )
+}
+
+/// Hexadecimal digits of a UUID, in its canonical 8-4-4-4-12 form
+const UUID: &str = "[0-9A-Fa-f]{8}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{12}";
+
+/// Match a UUID in its canonical form, like `550e8400-e29b-41d4-a716-446655440000`.
+/// Combine with `at_start()` and `must_end()` to match a whole input
+pub fn uuid() -> Pattern {
+    curated(Pattern::Raw(UUID.to_owned()), "uuid()")
+}
+
+/// Match a UUID between braces, like `{550e8400-e29b-41d4-a716-446655440000}`
+pub fn braced_uuid() -> Pattern {
+    curated(Pattern::Raw(format!(r"\{{{}\}}", UUID)), "braced_uuid()")
+}
+
+/// Match a key and a value separated by the given character, like `key=value`
+pub fn key_value<K: Into<Pattern>, V: Into<Pattern>>(key: K, value: V, sep: char) -> Pattern {
+    let (key, value) = (key.into(), value.into());
+    let code = format!(
+        "key_value({}, {}, {:?})",
+        key.to_argument_code(),
+        value.to_argument_code(),
+        sep
+    );
+    curated(
+        Pattern::Sequence(vec![key, Pattern::Raw(regex::escape(&sep.to_string())), value]),
+        code,
+    )
+}
+
+/// Match a list of key/value pairs separated by `&` or `;`, like a query string: `a=1&b=2`.
+/// The key and value patterns are repeated, so they should not contain named groups:
+/// use `key_value` to capture each pair
+pub fn key_value_list<K: Into<Pattern>, V: Into<Pattern>>(key: K, value: V, sep: char) -> Pattern {
+    let (key, value) = (key.into(), value.into());
+    let code = format!(
+        "key_value_list({}, {}, {:?})",
+        key.to_argument_code(),
+        value.to_argument_code(),
+        sep
+    );
+    let pair = key_value(key, value, sep);
+    let more = Pattern::Group {
+        exp: Box::new(Pattern::Sequence(vec![Pattern::Raw("[&;]".to_owned()), pair.clone()])),
+        capturing: false,
+    };
+    curated(Pattern::Sequence(vec![pair, more.many(0, 0)]), code)
+}
+
+/// Match the given pattern indented by exactly the given number of spaces: ` {n}exp`.
+/// With 0 spaces, the pattern is matched as is
+pub fn indented<T: Into<Pattern>>(spaces: u32, exp: T) -> Pattern {
+    let exp = exp.into();
+    let code = format!("indented({}, {})", spaces, exp.to_argument_code());
+    if spaces == 0 {
+        return curated(exp, code);
+    }
+    curated(Pattern::Sequence(vec![spaces_count(spaces, spaces), exp]), code)
+}
+
+/// Match the given pattern indented by at least the given number of spaces: ` {n,}exp`
+pub fn indented_at_least<T: Into<Pattern>>(spaces: u32, exp: T) -> Pattern {
+    let exp = exp.into();
+    let code = format!("indented_at_least({}, {})", spaces, exp.to_argument_code());
+    curated(Pattern::Sequence(vec![spaces_count(spaces, 0), exp]), code)
+}
+
+/// Match one of the given options, which must all have the same length.
+/// When the options are all the combinations of some characters at each position,
+/// the alternation is collapsed into one character class per position: `["ab", "cb"]` gives `[ac]b`
+pub fn fixed_alternation(options: &[&str]) -> Pattern {
+    assert!(!options.is_empty(), "no option to alternate");
+    let len = options[0].chars().count();
+    assert!(
+        options.iter().all(|o| o.chars().count() == len),
+        "options must all have the same length"
+    );
+    let code = format!("fixed_alternation(&{:?})", options);
+    let mut distinct: Vec<&str> = vec![];
+    let mut positions: Vec<Vec<char>> = vec![vec![]; len];
+    for o in options {
+        if !distinct.contains(o) {
+            distinct.push(o);
+        }
+        for (cs, c) in positions.iter_mut().zip(o.chars()) {
+            if !cs.contains(&c) {
+                cs.push(c);
+            }
+        }
+    }
+    let combinations = positions
+        .iter()
+        .try_fold(1usize, |acc, cs| acc.checked_mul(cs.len()));
+    let exp = if len > 0 && combinations == Some(distinct.len()) {
+        let mut exps: Vec<Pattern> = vec![];
+        for cs in positions {
+            match (cs.as_slice(), exps.last_mut()) {
+                ([c], Some(Pattern::Text(t))) => t.push(*c),
+                ([c], _) => exps.push(Pattern::Text(c.to_string())),
+                _ => exps.push(Pattern::Raw(format!(
+                    "[{}]",
+                    cs.iter()
+                        .map(|c| regex::escape(&c.to_string()))
+                        .collect::<String>()
+                ))),
+            }
+        }
+        if exps.len() == 1 {
+            exps.pop().unwrap()
+        } else {
+            Pattern::Sequence(exps)
+        }
+    } else {
+        Pattern::Group {
+            exp: Box::new(Pattern::Or(
+                distinct
+                    .into_iter()
+                    .map(|o| Pattern::Text(o.to_owned()))
+                    .collect(),
+            )),
+            capturing: false,
+        }
+    };
+    curated(exp, code)
+}
+
+/// Match a decimal number with an optional minus sign and optional decimals,
+/// using the given decimal separator and optional thousands separator:
+/// `decimal(',', Some('.'))` matches `1.234,56` and `1234,56`
+pub fn decimal(decimal_sep: char, thousands_sep: Option<char>) -> Pattern {
+    let code = format!("decimal({:?}, {:?})", decimal_sep, thousands_sep);
+    let integer = match thousands_sep {
+        Some(sep) => non_capturing(Pattern::Or(vec![
+            Pattern::Sequence(vec![
+                digits(1, 3),
+                Pattern::Many {
+                    exp: Box::new(non_capturing(Pattern::Sequence(vec![
+                        separator(sep),
+                        digits(3, 3),
+                    ]))),
+                    low: 1,
+                    high: 0,
+                    lazy: false,
+                },
+            ]),
+            digits(1, 0),
+        ])),
+        None => digits(1, 0),
+    };
+    curated(
+        Pattern::Sequence(vec![
+            Pattern::Many {
+                exp: Box::new(Pattern::Text("-".to_owned())),
+                low: 0,
+                high: 1,
+                lazy: false,
+            },
+            integer,
+            Pattern::Many {
+                exp: Box::new(non_capturing(Pattern::Sequence(vec![
+                    separator(decimal_sep),
+                    digits(1, 0),
+                ]))),
+                low: 0,
+                high: 1,
+                lazy: false,
+            },
+        ]),
+        code,
+    )
+}
+
+/// Match the given number of cells separated by whitespace, like the columns of a table:
+/// `cell(?:\s+cell){count-1}`. Use `named_columns` to capture each cell
+pub fn columns<T: Into<Pattern>>(count: usize, cell: T) -> Pattern {
+    assert!(count > 0, "at least one column is needed");
+    let cell = cell.into();
+    let code = format!("columns({}, {})", count, cell.to_argument_code());
+    let exp = if count == 1 {
+        cell
+    } else {
+        Pattern::Sequence(vec![
+            cell.clone(),
+            Pattern::Many {
+                exp: Box::new(non_capturing(Pattern::Sequence(vec![
+                    whitespace_run(),
+                    cell,
+                ]))),
+                low: count as u32 - 1,
+                high: count as u32 - 1,
+                lazy: false,
+            },
+        ])
+    };
+    curated(exp, code)
+}
+
+/// Match the given number of cells separated by whitespace, each cell captured
+/// in a group named after its index: `col_0`, `col_1`...
+pub fn named_columns<T: Into<Pattern>>(count: usize, cell: T) -> Pattern {
+    assert!(count > 0, "at least one column is needed");
+    let cell = cell.into();
+    let code = format!("named_columns({}, {})", count, cell.to_argument_code());
+    let mut exps = vec![];
+    for i in 0..count {
+        if i > 0 {
+            exps.push(whitespace_run());
+        }
+        exps.push(Pattern::Named {
+            exp: Box::new(cell.clone()),
+            name: format!("col_{}", i),
+        });
+    }
+    curated(Pattern::Sequence(exps), code)
+}
+
+/// Match a word: `\w+`
+pub fn word() -> Pattern {
+    curated(word_characters(), "word()")
+}
+
+/// Match words separated by whitespace, like a sentence without punctuation: `\w+(?:\s+\w+)*`
+pub fn words() -> Pattern {
+    curated(
+        Pattern::Sequence(vec![
+            word_characters(),
+            Pattern::Many {
+                exp: Box::new(non_capturing(Pattern::Sequence(vec![
+                    whitespace_run(),
+                    word_characters(),
+                ]))),
+                low: 0,
+                high: 0,
+                lazy: false,
+            },
+        ]),
+        "words()",
+    )
+}
+
+/// Word characters, at least once
+fn word_characters() -> Pattern {
+    Pattern::Many {
+        exp: Box::new(Pattern::WordCharacter),
+        low: 1,
+        high: 0,
+        lazy: false,
+    }
+}
+
+/// Match a list of items separated by the given separator, with at least one item:
+/// `item(?:sep item)*`. With the naive `(?:item sep)*item` form, a backtracking engine
+/// has to give back the last item and retry it after the loop; here each repetition
+/// starts with the separator, so there is nothing to give back. The `regex` crate does
+/// not backtrack, but the rendered regex may be used with other engines
+pub fn list_of<I: Into<Pattern>, S: Into<Pattern>>(item: I, sep: S) -> Pattern {
+    let (item, sep) = (item.into(), sep.into());
+    let code = format!(
+        "list_of({}, {})",
+        item.to_argument_code(),
+        sep.to_argument_code()
+    );
+    curated(separated(item, sep), code)
+}
+
+/// Match groups of digits of the given size, separated by the given character,
+/// like a credit card number: `grouped_digits(4, 4, '-')` gives `\d{4}(?:-\d{4}){3}`.
+/// Panics if the group size or the number of groups is 0
+pub fn grouped_digits(group_size: u32, groups: u32, sep: char) -> Pattern {
+    assert!(group_size > 0, "groups need at least one digit");
+    assert!(groups > 0, "at least one group is needed");
+    let code = format!("grouped_digits({}, {}, {:?})", group_size, groups, sep);
+    let group = digits(group_size, group_size);
+    let exp = if groups == 1 {
+        group
+    } else {
+        Pattern::Sequence(vec![
+            group.clone(),
+            Pattern::Many {
+                exp: Box::new(non_capturing(Pattern::Sequence(vec![separator(sep), group]))),
+                low: groups - 1,
+                high: groups - 1,
+                lazy: false,
+            },
+        ])
+    };
+    curated(exp, code)
+}
+
+/// Match between one and max lines, each ending with a new line: `(?:line\n){1,max}`.
+/// Use `repeated_lines_ending` for other line endings
+pub fn repeated_lines<T: Into<Pattern>>(line: T, max: u32) -> Pattern {
+    let line = line.into();
+    let code = format!("repeated_lines({}, {})", line.to_argument_code(), max);
+    curated(lines(line, max, "\n"), code)
+}
+
+/// Match between one and max lines, each ending with the given text, like `"\r\n"`:
+/// `(?:line\r\n){1,max}`
+pub fn repeated_lines_ending<T: Into<Pattern>>(line: T, max: u32, ending: &str) -> Pattern {
+    let line = line.into();
+    let code = format!(
+        "repeated_lines_ending({}, {}, {:?})",
+        line.to_argument_code(),
+        max,
+        ending
+    );
+    curated(lines(line, max, ending), code)
+}
+
+/// Lines followed by the line ending, between one and max times
+fn lines(line: Pattern, max: u32, ending: &str) -> Pattern {
+    assert!(max > 0, "at least one line is needed");
+    let ending = ending
+        .chars()
+        .map(|c| match c {
+            '\n' => r"\n".to_owned(),
+            '\r' => r"\r".to_owned(),
+            '\t' => r"\t".to_owned(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect::<String>();
+    Pattern::Many {
+        exp: Box::new(non_capturing(Pattern::Sequence(vec![
+            line,
+            Pattern::Raw(ending),
+        ]))),
+        low: 1,
+        high: max,
+        lazy: false,
+    }
+}
+
+/// One or more whitespace characters
+fn whitespace_run() -> Pattern {
+    Pattern::Raw(r"\s+".to_owned())
+}
+
+/// Digits, between low and high times
+fn digits(low: u32, high: u32) -> Pattern {
+    Pattern::Many {
+        exp: Box::new(Pattern::Digit),
+        low,
+        high,
+        lazy: false,
+    }
+}
+
+/// A separator character, escaped
+fn separator(sep: char) -> Pattern {
+    Pattern::Raw(regex::escape(&sep.to_string()))
+}
+
+/// Non capturing group around the given pattern
+fn non_capturing(exp: Pattern) -> Pattern {
+    Pattern::Group {
+        exp: Box::new(exp),
+        capturing: false,
+    }
+}
+
+/// Match exactly one of the given names, like the variants of a serialized enum:
+/// `^(?:Green|Blue|Red)$`. Names are escaped, and sorted longest first so a name
+/// that is a prefix of another never shadows it
+pub fn from_variants(names: &[&str]) -> Pattern {
+    assert!(!names.is_empty(), "no variant to match");
+    let code = format!("from_variants(&{:?})", names);
+    let mut sorted = names.to_vec();
+    sorted.sort_by_key(|n| std::cmp::Reverse(n.chars().count()));
+    curated(
+        Pattern::Sequence(vec![
+            Pattern::InputStart,
+            non_capturing(Pattern::Or(
+                sorted
+                    .into_iter()
+                    .map(|n| Pattern::Raw(regex::escape(n)))
+                    .collect(),
+            )),
+            Pattern::InputEnd,
+        ]),
+        code,
+    )
+}
+
+/// Match an integer between min and max, inclusive, without leading zeros:
+/// `bounded_integer(1, 31)` gives `(?:[1-2][0-9]|3[0-1]|[1-9])`.
+/// Longer alternatives come first, but the pattern still matches the start of a longer
+/// number, so anchor it or surround it with separators
+pub fn bounded_integer(min: i64, max: i64) -> Pattern {
+    assert!(min <= max, "invalid bounds: {} is more than {}", min, max);
+    let code = format!("bounded_integer({}, {})", min, max);
+    let mut alternatives = vec![];
+    if min < 0 {
+        let closest = if max < 0 { max.unsigned_abs() } else { 1 };
+        alternatives.extend(
+            unsigned_range(closest, min.unsigned_abs())
+                .into_iter()
+                .map(|a| format!("-{}", a)),
+        );
+    }
+    if max >= 0 {
+        alternatives.extend(unsigned_range(min.max(0) as u64, max as u64));
+    }
+    curated(
+        non_capturing(Pattern::Or(
+            alternatives.into_iter().map(Pattern::Raw).collect(),
+        )),
+        code,
+    )
+}
+
+/// Alternatives matching the numbers between low and high, longer numbers first
+fn unsigned_range(low: u64, high: u64) -> Vec<String> {
+    let (low, high) = (low.to_string(), high.to_string());
+    let mut alternatives = vec![];
+    for len in (low.len()..=high.len()).rev() {
+        let from = if len == low.len() {
+            low.clone()
+        } else {
+            format!("1{}", "0".repeat(len - 1))
+        };
+        let to = if len == high.len() {
+            high.clone()
+        } else {
+            "9".repeat(len)
+        };
+        alternatives.extend(same_length_range(&from, &to));
+    }
+    alternatives
+}
+
+/// Alternatives matching the numbers between two numbers of the same length
+fn same_length_range(from: &str, to: &str) -> Vec<String> {
+    if from == to {
+        return vec![from.to_owned()];
+    }
+    let (f0, t0) = (from.as_bytes()[0] as char, to.as_bytes()[0] as char);
+    let (from_rest, to_rest) = (&from[1..], &to[1..]);
+    if from_rest.is_empty() {
+        return vec![format!("[{}-{}]", f0, t0)];
+    }
+    if f0 == t0 {
+        return same_length_range(from_rest, to_rest)
+            .into_iter()
+            .map(|a| format!("{}{}", f0, a))
+            .collect();
+    }
+    let mut alternatives = vec![];
+    let mut start = f0;
+    if from_rest.chars().any(|c| c != '0') {
+        alternatives.extend(
+            same_length_range(from_rest, &"9".repeat(from_rest.len()))
+                .into_iter()
+                .map(|a| format!("{}{}", f0, a)),
+        );
+        start = (f0 as u8 + 1) as char;
+    }
+    let mut end = t0;
+    let mut last = vec![];
+    if to_rest.chars().any(|c| c != '9') {
+        last = same_length_range(&"0".repeat(to_rest.len()), to_rest)
+            .into_iter()
+            .map(|a| format!("{}{}", t0, a))
+            .collect();
+        end = (t0 as u8 - 1) as char;
+    }
+    if start <= end {
+        let digits = if start == end {
+            start.to_string()
+        } else {
+            format!("[{}-{}]", start, end)
+        };
+        let rest = match to_rest.len() {
+            1 => "[0-9]".to_owned(),
+            n => format!("[0-9]{{{}}}", n),
+        };
+        alternatives.push(format!("{}{}", digits, rest));
+    }
+    alternatives.extend(last);
+    alternatives
+}
+
+/// Match content between the open and close characters, where the escape character protects
+/// the next character, like a string literal: `delimited('"', '"', '\\')` matches `"a\"b"`.
+/// When the escape is the close character, a doubled close character stands for itself,
+/// like quotes in SQL strings: `'it''s'`
+pub fn delimited(open: char, close: char, escape: char) -> Pattern {
+    let code = format!("delimited({:?}, {:?}, {:?})", open, close, escape);
+    let (o, c, e) = (
+        regex::escape(&open.to_string()),
+        regex::escape(&close.to_string()),
+        regex::escape(&escape.to_string()),
+    );
+    let content = if escape == close {
+        format!("(?:[^{}]|{}{})*", class_escape(close), c, c)
+    } else {
+        format!(
+            r"(?:[^{}{}]|{}[\s\S])*",
+            class_escape(close),
+            class_escape(escape),
+            e
+        )
+    };
+    curated(Pattern::Raw(format!("{}{}{}", o, content, c)), code)
+}
+
+/// Spaces, between low and high times
+fn spaces_count(low: u32, high: u32) -> Pattern {
+    Pattern::Many {
+        exp: Box::new(Pattern::Text(" ".to_owned())),
+        low,
+        high,
+        lazy: false,
+    }
+}
+
+/// Any character, zero or more times
+fn any_count() -> Pattern {
+    Pattern::Many {
+        exp: Box::new(Pattern::Any),
+        low: 0,
+        high: 0,
+        lazy: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{anything, at_start, digit, start_with, text, word_character, ToCode};
+    use regex::{Regex, RegexBuilder};
+
+    #[test]
+    fn test_starts_with_anchored() {
+        let p = starts_with_anchored("abc");
+        assert_eq!("^abc", p.to_string());
+        assert_eq!(r#"starts_with_anchored("abc")"#, p.to_code());
+        assert_eq!("abc", start_with("abc").to_string());
+        let p = starts_with_anchored("abc").and_then(digit());
+        assert_eq!(r"^abc\d", p.to_string());
+        assert_eq!(r#"starts_with_anchored("abc").and_then(digit())"#, p.to_code());
+        let re = Regex::new(&p.to_string()).unwrap();
+        assert!(re.is_match("abc1 and more"));
+        assert!(!re.is_match("xabc1"));
+    }
+
+    #[test]
+    fn test_whole_line() {
+        assert_eq!("^.*$", whole_line().to_string());
+        assert_eq!("whole_line()", whole_line().to_code());
+        let p = whole_line_containing(text("ERROR"));
+        assert_eq!("^.*ERROR.*$", p.to_string());
+        assert_eq!(r#"whole_line_containing("ERROR")"#, p.to_code());
+
+        let log = "INFO start\nERROR failed\nINFO done";
+        let re = RegexBuilder::new(&p.to_string())
+            .multi_line(true)
+            .build()
+            .unwrap();
+        let lines: Vec<&str> = re.find_iter(log).map(|m| m.as_str()).collect();
+        assert_eq!(vec!["ERROR failed"], lines);
+        let re = RegexBuilder::new(&p.to_string()).build().unwrap();
+        assert!(!re.is_match(log));
+    }
+
+    #[test]
+    fn test_anything_including_newline() {
+        let p = start_with("a").and_then(anything_including_newline()).and_then("b");
+        assert_eq!(r"a[\s\S]b", p.to_string());
+        assert_eq!(r"[\s\S]*", anything_including_newline().many(0, 0).to_string());
+        assert_eq!(
+            r#"start_with("a").and_then(anything_including_newline()).and_then("b")"#,
+            p.to_code()
+        );
+        let re = Regex::new(&p.to_string()).unwrap();
+        assert!(re.is_match("a\nb"));
+        assert!(!Regex::new(&start_with("a").and_then(anything()).and_then("b").to_string())
+            .unwrap()
+            .is_match("a\nb"));
+    }
+
+    #[test]
+    fn test_fixed_alternation() {
+        let p = fixed_alternation(&["ab", "cb"]);
+        assert_eq!("[ac]b", p.to_string());
+        assert_eq!(r#"fixed_alternation(&["ab", "cb"])"#, p.to_code());
+        assert_eq!(
+            r"x[ab][\-c]",
+            fixed_alternation(&["xa-", "xac", "xb-", "xbc"]).to_string()
+        );
+
+        let p = start_with("<").and_then(fixed_alternation(&["ab", "cd"])).and_then(">");
+        assert_eq!("<(?:ab|cd)>", p.to_string());
+        let re = Regex::new(&p.to_string()).unwrap();
+        assert!(re.is_match("<cd>"));
+        assert!(!re.is_match("<ad>"));
+    }
+
+    #[test]
+    fn test_decimal() {
+        let european = decimal(',', Some('.'));
+        assert_eq!(r"-?(?:\d{1,3}(?:\.\d{3})+|\d+)(?:,\d+)?", european.to_string());
+        assert_eq!("decimal(',', Some('.'))", european.to_code());
+        let re = Regex::new(&at_start().and_then(european).must_end().to_string()).unwrap();
+        assert!(re.is_match("1.234,56"));
+        assert!(re.is_match("-1234,5"));
+        assert!(!re.is_match("1,234.56"));
+
+        let us = decimal('.', Some(','));
+        let re = Regex::new(&at_start().and_then(us).must_end().to_string()).unwrap();
+        assert!(re.is_match("1,234.56"));
+        assert!(re.is_match("1234"));
+        assert!(!re.is_match("1.234,56"));
+
+        assert_eq!(r"-?\d+(?:\.\d+)?", decimal('.', None).to_string());
+    }
+
+    #[test]
+    fn test_columns() {
+        let cell = word_character().many(1, 0);
+        let p = columns(3, cell.clone());
+        assert_eq!(r"\w+(?:\s+\w+){2}", p.to_string());
+        assert_eq!("columns(3, word_character().one_or_more())", p.to_code());
+        assert!(Regex::new(&p.to_string()).unwrap().is_match("a  b\tc"));
+
+        let p = named_columns(3, cell);
+        assert_eq!(
+            r"(?P<col_0>\w+)\s+(?P<col_1>\w+)\s+(?P<col_2>\w+)",
+            p.to_string()
+        );
+        assert_eq!("named_columns(3, word_character().one_or_more())", p.to_code());
+        let caps = Regex::new(&p.to_string())
+            .unwrap()
+            .captures("alpha  beta\tgamma")
+            .unwrap();
+        assert_eq!("alpha", &caps["col_0"]);
+        assert_eq!("beta", &caps["col_1"]);
+        assert_eq!("gamma", &caps["col_2"]);
+    }
+
+    #[test]
+    fn test_text_ci() {
+        let p = start_with("id=").and_then(text_ci("abc"));
+        assert_eq!("id=(?i:abc)", p.to_string());
+        assert_eq!(r#"start_with("id=").and_then(text_ci("abc"))"#, p.to_code());
+        let re = Regex::new(&p.to_string()).unwrap();
+        assert!(re.is_match("id=ABC"));
+        assert!(re.is_match("id=aBc"));
+        assert!(!Regex::new("id=ABC").unwrap().is_match("id=abc"));
+
+        let kelvin = "\u{212A}";
+        assert!(Regex::new(&text_ci("k").to_string()).unwrap().is_match(kelvin));
+        assert!(!Regex::new("[kK]").unwrap().is_match(kelvin));
+        assert!(!Regex::new(&text_ci("i").to_string()).unwrap().is_match("\u{130}"));
+
+        let p = text_ci("ab").one_or_more();
+        assert_eq!("(?i:ab)+", p.to_string());
+        assert!(p.compile().unwrap().is_match("AbaB"));
+        assert_eq!("'ab' (case insensitive) one or more times", p.explain_text());
+    }
+
+    #[test]
+    fn test_grouped_digits() {
+        let p = grouped_digits(4, 4, '-');
+        assert_eq!(r"\d{4}(?:\-\d{4}){3}", p.to_string());
+        assert_eq!("grouped_digits(4, 4, '-')", p.to_code());
+        let re = Regex::new(&at_start().and_then(p).must_end().to_string()).unwrap();
+        assert!(re.is_match("1234-5678-9012-3456"));
+        assert!(!re.is_match("1234-5678-9012-345"));
+        assert!(!re.is_match("12345-678-9012-3456"));
+        assert!(!re.is_match("1234-5678-9012"));
+        assert_eq!(r"\d{3}", grouped_digits(3, 1, ' ').to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "groups need at least one digit")]
+    fn test_grouped_digits_empty_group() {
+        grouped_digits(0, 2, ',');
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(r"\w+", word().to_string());
+        assert_eq!("word()", word().to_code());
+        let re = Regex::new(&word().to_string()).unwrap();
+        assert_eq!("hello", re.find("  hello world").unwrap().as_str());
+
+        assert_eq!(r"\w+(?:\s+\w+)*", words().to_string());
+        assert_eq!(
+            r#"start_with("> ").and_then(words())"#,
+            start_with("> ").and_then(words()).to_code()
+        );
+        let re = Regex::new(&words().to_string()).unwrap();
+        assert_eq!(
+            "the quick  brown fox",
+            re.find("the quick  brown fox.").unwrap().as_str()
+        );
+    }
+
+    #[test]
+    fn test_bom() {
+        assert_eq!(r"\x{FEFF}", byte_order_mark().to_string());
+        let p = at_start().and_then(with_optional_bom("key="));
+        assert_eq!(r"^\x{FEFF}?key=", p.to_string());
+        assert_eq!(r#"at_start().and_then(with_optional_bom("key="))"#, p.to_code());
+        let re = p.compile_bytes().unwrap();
+        assert!(re.is_match(b"\xEF\xBB\xBFkey=1"));
+        assert!(re.is_match(b"key=1"));
+        assert!(!re.is_match(b"\xEFkey=1"));
+    }
+
+    #[test]
+    fn test_list_of() {
+        let p = list_of(digit().many(1, 0), ",");
+        assert_eq!(r"\d+(?:,\d+)*", p.to_string());
+        assert_eq!(r#"list_of(digit().one_or_more(), ",")"#, p.to_code());
+        let re = Regex::new(&at_start().and_then(p).must_end().to_string()).unwrap();
+        assert!(re.is_match("1,2,3"));
+        assert!(re.is_match("1"));
+        assert!(!re.is_match("1,2,"));
+        assert!(!re.is_match(""));
+    }
+
+    #[test]
+    fn test_repeated_lines() {
+        let p = repeated_lines(word_character().many(1, 0), 3);
+        assert_eq!(r"(?:\w+\n){1,3}", p.to_string());
+        assert_eq!("repeated_lines(word_character().one_or_more(), 3)", p.to_code());
+        let re = Regex::new(&at_start().and_then(p).must_end().to_string()).unwrap();
+        assert!(re.is_match("a\nb\n"));
+        assert!(re.is_match("a\nb\nc\n"));
+        assert!(!re.is_match("a\nb\nc\nd\n"));
+        assert!(!re.is_match(""));
+
+        let p = repeated_lines_ending(word_character().many(1, 0), 2, "\r\n");
+        assert_eq!(r"(?:\w+\r\n){1,2}", p.to_string());
+        assert_eq!(
+            r#"repeated_lines_ending(word_character().one_or_more(), 2, "\r\n")"#,
+            p.to_code()
+        );
+        let re = Regex::new(&at_start().and_then(p).must_end().to_string()).unwrap();
+        assert!(re.is_match("a\r\nb\r\n"));
+        assert!(!re.is_match("a\nb\n"));
+        assert!(!re.is_match("a\r\nb\r\nc\r\n"));
+    }
+
+    #[test]
+    fn test_from_variants() {
+        let p = from_variants(&["Red", "Green", "Blue"]);
+        assert_eq!("^(?:Green|Blue|Red)$", p.to_string());
+        assert_eq!(r#"from_variants(&["Red", "Green", "Blue"])"#, p.to_code());
+        let re = Regex::new(&p.to_string()).unwrap();
+        for v in &["Red", "Green", "Blue"] {
+            assert!(re.is_match(v));
+        }
+        assert!(!re.is_match("Yellow"));
+        assert!(!re.is_match("Redish"));
+        assert_eq!(
+            r"^(?:A\.B|AB|A)$",
+            from_variants(&["A", "AB", "A.B"]).to_string()
+        );
+    }
+
+    #[test]
+    fn test_bounded_integer() {
+        let p = bounded_integer(1, 31);
+        assert_eq!("(?:[1-2][0-9]|3[0-1]|[1-9])", p.to_string());
+        assert_eq!("bounded_integer(1, 31)", p.to_code());
+        let re = Regex::new(&at_start().and_then(p).must_end().to_string()).unwrap();
+        for day in &["1", "9", "10", "29", "30", "31"] {
+            assert!(re.is_match(day), "{}", day);
+        }
+        for day in &["0", "32", "40", "01", "100", "-1"] {
+            assert!(!re.is_match(day), "{}", day);
+        }
+
+        for (min, max) in &[(0, 255), (-40, 125), (-300, -7), (7, 1234), (0, 0)] {
+            let p = bounded_integer(*min, *max);
+            let re = Regex::new(&at_start().and_then(p).must_end().to_string()).unwrap();
+            for n in (min - 50)..=(max + 50) {
+                assert_eq!(n >= *min && n <= *max, re.is_match(&n.to_string()), "{}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_delimited() {
+        let p = delimited('`', '`', '\\');
+        assert_eq!(r"`(?:[^`\\]|\\[\s\S])*`", p.to_string());
+        assert_eq!(r"delimited('`', '`', '\\')", p.to_code());
+        let re = Regex::new(&p.to_string()).unwrap();
+        assert_eq!(
+            r"`a\`b`",
+            re.find(r"x = `a\`b` + `c`").unwrap().as_str()
+        );
+        assert!(!re.is_match("`abc"));
+
+        let re = Regex::new(&delimited('\'', '\'', '\'').to_string()).unwrap();
+        assert_eq!("'it''s'", re.find("s = 'it''s';").unwrap().as_str());
+        let re = Regex::new(&delimited('[', ']', '\\').to_string()).unwrap();
+        assert_eq!(r"[a\]b]", re.find(r"x[a\]b]y").unwrap().as_str());
+    }
+
+    #[test]
+    fn test_unicode_block() {
+        let p = unicode_block("Greek");
+        assert_eq!(r"[\x{0370}-\x{03FF}]", p.to_string());
+        assert_eq!(r#"unicode_block("Greek")"#, p.to_code());
+        let re = Regex::new(&p.to_string()).unwrap();
+        assert!(re.is_match("λ"));
+        assert!(!re.is_match("l"));
+
+        let re = Regex::new(&unicode_block("cjk unified-ideographs").many(1, 0).to_string())
+            .unwrap();
+        assert_eq!("漢字", re.find("abc漢字def").unwrap().as_str());
+        let re = Regex::new(&unicode_block("Emoticons").to_string()).unwrap();
+        assert!(re.is_match("\u{1F600}"));
+
+        let p = unicode_block("Han");
+        assert_eq!(r"\p{Han}", p.to_string());
+        assert!(Regex::new(&p.to_string()).unwrap().is_match("漢"));
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!("0[xX][0-9A-Fa-f]+", hex_number().to_string());
+        assert_eq!("hex_number()", hex_number().to_code());
+        assert_eq!("0[bB][01]+", binary_number().to_string());
+        assert_eq!(
+            "at_start().and_then(binary_number()).must_end()",
+            at_start().and_then(binary_number()).must_end().to_code()
+        );
+
+        let hex = Regex::new(&at_start().and_then(hex_number()).must_end().to_string()).unwrap();
+        assert!(hex.is_match("0xFF"));
+        assert!(hex.is_match("0X1a"));
+        assert!(!hex.is_match("0x"));
+        let bin = Regex::new(&at_start().and_then(binary_number()).must_end().to_string()).unwrap();
+        assert!(bin.is_match("0b1010"));
+        assert!(!bin.is_match("0b2"));
+    }
+
+    #[test]
+    fn test_key_value() {
+        let p = key_value(word_character().many(1, 0), digit().many(1, 0), '=');
+        assert_eq!(r"\w+=\d+", p.to_string());
+        assert_eq!(
+            "key_value(word_character().one_or_more(), digit().one_or_more(), '=')",
+            p.to_code()
+        );
+        let list = key_value_list(word_character().many(1, 0), digit().many(1, 0), '=');
+        assert_eq!(r"\w+=\d+(?:[&;]\w+=\d+)*", list.to_string());
+        let re = Regex::new(&at_start().and_then(list).must_end().to_string()).unwrap();
+        assert!(re.is_match("a=1&b=2"));
+        assert!(re.is_match("a=1;b=2"));
+        assert!(!re.is_match("a=1&b"));
+
+        let pair = key_value(
+            word_character().many(1, 0).named("key"),
+            digit().many(1, 0).named("value"),
+            '=',
+        );
+        let re = Regex::new(&pair.to_string()).unwrap();
+        let pairs: Vec<(String, String)> = re
+            .captures_iter("a=1&b=2")
+            .map(|c| (c["key"].to_owned(), c["value"].to_owned()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("a".to_owned(), "1".to_owned()),
+                ("b".to_owned(), "2".to_owned())
+            ],
+            pairs
+        );
+    }
+
+    #[test]
+    fn test_ascii() {
+        assert_eq!("[0-9]", ascii_digit().to_string());
+        assert_eq!("[a-zA-Z]", ascii_letter().to_string());
+        assert_eq!("[0-9A-Za-z_]", ascii_word_character().to_string());
+        assert_eq!(
+            "ascii_digit().and_then(ascii_letter())",
+            ascii_digit().and_then(ascii_letter()).to_code()
+        );
+        let re = Regex::new(&ascii_digit().to_string()).unwrap();
+        assert!(re.is_match("7"));
+        assert!(!re.is_match("\u{0663}"));
+    }
+
+    #[test]
+    fn test_mac_address() {
+        assert_eq!("([0-9A-Fa-f]{2}[:-]){5}[0-9A-Fa-f]{2}", mac_address().to_string());
+        assert_eq!("mac_address()", mac_address().to_code());
+        let re = Regex::new(&at_start().and_then(mac_address()).must_end().to_string()).unwrap();
+        assert!(re.is_match("01:23:45:67:89:AB"));
+        assert!(re.is_match("01-23-45-67-89-ab"));
+        assert!(!re.is_match("01:23:45:67:89"));
+        assert!(!re.is_match("01:23:45:67:89:A"));
+    }
+
+    #[test]
+    fn test_uuid() {
+        assert_eq!("uuid()", uuid().to_code());
+        assert_eq!(
+            r"\{[0-9A-Fa-f]{8}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{12}\}",
+            braced_uuid().to_string()
+        );
+        let re = Regex::new(&at_start().and_then(uuid()).must_end().to_string()).unwrap();
+        assert!(re.is_match("550e8400-e29b-41d4-a716-446655440000"));
+        assert!(!re.is_match("550e8400-e29b-41d4-a716-44665544000"));
+        assert!(!re.is_match("550e8400e29b41d4a716446655440000"));
+        let re = Regex::new(&braced_uuid().to_string()).unwrap();
+        assert!(re.is_match("{550E8400-E29B-41D4-A716-446655440000}"));
+    }
+
+    #[test]
+    fn test_indented() {
+        let p = indented(4, word_character().many(1, 0));
+        assert_eq!(r" {4}\w+", p.to_string());
+        assert_eq!("indented(4, word_character().one_or_more())", p.to_code());
+        let re = Regex::new(&at_start().and_then(p).to_string()).unwrap();
+        assert!(re.is_match("    content"));
+        assert!(!re.is_match("  content"));
+        assert!(!re.is_match("      content"));
+
+        let p = indented_at_least(4, word_character().many(1, 0));
+        assert_eq!(r" {4,}\w+", p.to_string());
+        let re = Regex::new(&at_start().and_then(p).to_string()).unwrap();
+        assert!(re.is_match("      content"));
+        assert!(!re.is_match("  content"));
+
+        let p = indented(0, word_character().many(1, 0));
+        assert_eq!(r"\w+", p.to_string());
+        assert_eq!("indented(0, word_character().one_or_more())", p.to_code());
+        let re = Regex::new(&at_start().and_then(p).to_string()).unwrap();
+        assert!(re.is_match("content"));
+        assert!(!re.is_match(" content"));
+    }
+}
@@ -0,0 +1,289 @@
+//! Describe patterns in plain English
+
+use crate::builder::{BackRefKind, ClassItem, Pattern};
+use itertools::Itertools;
+
+impl Pattern {
+    /// Describe what the pattern matches in plain English, for documentation or debugging:
+    /// the date pattern `\d{4}-\d{2}-\d{2}` gives
+    /// `4 digits, then '-', then 2 digits, then '-', then 2 digits`
+    pub fn explain_text(&self) -> String {
+        match self {
+            Pattern::Sequence(exps) => match exps.as_slice() {
+                [] => "nothing".to_string(),
+                [exp] => exp.explain_text(),
+                exps => exps.iter().map(Pattern::explain_text).join(", then "),
+            },
+            Pattern::Text(t) if t.is_empty() => "nothing".to_string(),
+            Pattern::Text(t) => quote(t),
+            Pattern::Raw(r) if r.is_empty() => "nothing".to_string(),
+            Pattern::Raw(r) => format!("the regex `{}`", r),
+            Pattern::Or(exps) => match exps.as_slice() {
+                [] => "nothing".to_string(),
+                [exp] => exp.explain_text(),
+                [init @ .., last] => format!(
+                    "either {} or {}",
+                    init.iter().map(Pattern::nested_text).join(", "),
+                    last.nested_text()
+                ),
+            },
+            Pattern::Many {
+                exp,
+                low,
+                high,
+                lazy,
+            } => {
+                let text = match exp.class_nouns() {
+                    Some((singular, plural)) => match (low, high) {
+                        (0, 1) => format!("optionally a {}", singular),
+                        (0, 0) => format!("any number of {}", plural),
+                        (1, 0) => format!("one or more {}", plural),
+                        (1, 1) => format!("1 {}", singular),
+                        (l, 0) => format!("at least {} {}", l, plural),
+                        (0, h) => format!("at most {} {}", h, plural),
+                        (l, h) if l == h => format!("{} {}", l, plural),
+                        (l, h) => format!("{} to {} {}", l, h, plural),
+                    },
+                    None => {
+                        let text = exp.nested_text();
+                        match (low, high) {
+                            (0, 1) => format!("optionally {}", text),
+                            (0, 0) => format!("{} any number of times", text),
+                            (1, 0) => format!("{} one or more times", text),
+                            (1, 1) => text,
+                            (l, 0) => format!("{} at least {} times", text, l),
+                            (0, h) => format!("{} at most {} times", text, h),
+                            (l, h) if l == h => format!("{} {} times", text, l),
+                            (l, h) => format!("{} {} to {} times", text, l, h),
+                        }
+                    }
+                };
+                if *lazy {
+                    format!("{} (as few as possible)", text)
+                } else {
+                    text
+                }
+            }
+            Pattern::Any => "any character".to_string(),
+            Pattern::InputStart => "the start of input".to_string(),
+            Pattern::InputEnd => "the end of input".to_string(),
+            Pattern::TextStart => "the start of the text".to_string(),
+            Pattern::TextEnd => "the end of the text".to_string(),
+            Pattern::WordBoundary { negated: false } => "a word boundary".to_string(),
+            Pattern::WordBoundary { negated: true } => "no word boundary".to_string(),
+            Pattern::Not(exp) => format!("any character except {}", exp.nested_text()),
+            Pattern::Named { exp, name } => {
+                format!("{} (captured as {})", exp.explain_text(), quote(name))
+            }
+            Pattern::Control('\t') => "a tab".to_string(),
+            Pattern::Control('\n') => "a newline".to_string(),
+            Pattern::Control('\r') => "a carriage return".to_string(),
+            Pattern::Control(c) => {
+                format!("the control character ^{}", ((*c as u8) | 0x40) as char)
+            }
+            Pattern::HexChar(code) => format!("the character U+{:04X}", code),
+            Pattern::BackRef(BackRefKind::Name(name)) => {
+                format!("the text captured as {}", quote(name))
+            }
+            Pattern::BackRef(BackRefKind::Index(index)) => {
+                format!("the text captured by group {}", index)
+            }
+            Pattern::Curated { exp, .. } => exp.explain_text(),
+            Pattern::Group {
+                exp,
+                capturing: true,
+            } => format!("{} (captured)", exp.explain_text()),
+            Pattern::Group { exp, .. } => exp.explain_text(),
+            Pattern::LookAhead { exp, negated } => format!(
+                "{} by {}",
+                if *negated { "not followed" } else { "followed" },
+                exp.nested_text()
+            ),
+            Pattern::LookBehind { exp, negated } => format!(
+                "{} by {}",
+                if *negated { "not preceded" } else { "preceded" },
+                exp.nested_text()
+            ),
+            Pattern::Flags { flags, exp } => {
+                let flag_text = |c| match c {
+                    'i' => "case insensitive".to_string(),
+                    'm' => "multi-line".to_string(),
+                    's' => "dot matches newline".to_string(),
+                    'x' => "ignoring whitespace".to_string(),
+                    'U' => "lazy by default".to_string(),
+                    'u' => "Unicode".to_string(),
+                    c => format!("flag {}", c),
+                };
+                let (on, off) = flags.split_at(flags.find('-').unwrap_or(flags.len()));
+                format!(
+                    "{} ({})",
+                    exp.explain_text(),
+                    on.chars()
+                        .map(flag_text)
+                        .chain(off.chars().skip(1).map(|c| format!("not {}", flag_text(c))))
+                        .join(", ")
+                )
+            }
+            Pattern::CharSet {
+                items,
+                negated: false,
+            } => format!("one of {}", items_text(items)),
+            Pattern::CharSet {
+                items,
+                negated: true,
+            } => format!("any character except {}", items_text(items)),
+            p => match p.class_nouns() {
+                Some((singular, _)) => format!("a {}", singular),
+                None => "nothing".to_string(),
+            },
+        }
+    }
+
+    /// Description of a sub-pattern, in parentheses if it is made of several parts
+    fn nested_text(&self) -> String {
+        match self {
+            Pattern::Sequence(exps) | Pattern::Or(exps) if exps.len() > 1 => {
+                format!("({})", self.explain_text())
+            }
+            _ => self.explain_text(),
+        }
+    }
+
+    /// Singular and plural nouns for the patterns matching one character of a class,
+    /// so repetitions read as `4 digits` instead of `a digit 4 times`
+    fn class_nouns(&self) -> Option<(String, String)> {
+        let nouns = |singular: &str| Some((singular.to_string(), format!("{}s", singular)));
+        match self {
+            Pattern::Digit => nouns("digit"),
+            Pattern::Letter => nouns("letter"),
+            Pattern::Number => nouns("numeric character"),
+            Pattern::WordCharacter => nouns("word character"),
+            Pattern::Whitespace => nouns("whitespace character"),
+            Pattern::Any => nouns("character"),
+            Pattern::PosixClass(name) => nouns(&format!("POSIX {} character", name)),
+            Pattern::UnicodeClass { name, negated } => Some((
+                format!(
+                    "character {}in Unicode class {}",
+                    if *negated { "not " } else { "" },
+                    name
+                ),
+                format!(
+                    "characters {}in Unicode class {}",
+                    if *negated { "not " } else { "" },
+                    name
+                ),
+            )),
+            Pattern::CharRange { from, to } => Some((
+                format!(
+                    "character between {} and {}",
+                    quote_char(*from),
+                    quote_char(*to)
+                ),
+                format!(
+                    "characters between {} and {}",
+                    quote_char(*from),
+                    quote_char(*to)
+                ),
+            )),
+            Pattern::Curated { exp, .. } => exp.class_nouns(),
+            _ => None,
+        }
+    }
+}
+
+/// Quote a text
+fn quote(text: &str) -> String {
+    format!("'{}'", text.escape_debug())
+}
+
+/// Quote a character
+fn quote_char(c: char) -> String {
+    format!("'{}'", c.escape_debug())
+}
+
+/// List the items of a class: `'a' to 'c', 'x' or 'y'`
+fn items_text(items: &[ClassItem]) -> String {
+    let item_text = |i: &ClassItem| match i {
+        ClassItem::Char(c) => quote_char(*c),
+        ClassItem::Range(from, to) => format!("{} to {}", quote_char(*from), quote_char(*to)),
+    };
+    match items {
+        [] => "nothing".to_string(),
+        [i] => item_text(i),
+        [init @ .., last] => format!(
+            "{} or {}",
+            init.iter().map(item_text).join(", "),
+            item_text(last)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::*;
+    use crate::parser::explain;
+
+    #[test]
+    fn test_explain_text_date() {
+        let p = explain(r"\d{4}-\d{2}-\d{2}").unwrap();
+        assert_eq!(
+            "4 digits, then '-', then 2 digits, then '-', then 2 digits",
+            p.explain_text()
+        );
+        let p = at_start()
+            .and_then(digit().times(4).named("year"))
+            .and_then("-")
+            .and_then(digit().many(1, 2).named("month"))
+            .must_end();
+        assert_eq!(
+            "the start of input, then 4 digits (captured as 'year'), then '-', \
+             then 1 to 2 digits (captured as 'month'), then the end of input",
+            p.explain_text()
+        );
+    }
+
+    #[test]
+    fn test_explain_text_alternation() {
+        assert_eq!(
+            "either 'cat' or 'dog'",
+            either(("cat", "dog")).explain_text()
+        );
+        let p = start_with("a").and_either(("b", start_with(digit()).and_then("c"), letter()));
+        assert_eq!(
+            "'a', then either 'b', (a digit, then 'c') or a letter",
+            p.explain_text()
+        );
+        assert_eq!(
+            "(either 'a' or 'b') one or more times",
+            either(("a", "b")).many(1, 0).explain_text()
+        );
+    }
+
+    #[test]
+    fn test_explain_text_classes() {
+        assert_eq!(
+            "one of 'a', 'e' or 'i'",
+            one_of(['a', 'e', 'i']).explain_text()
+        );
+        assert_eq!(
+            "any number of characters between 'a' and 'z' (as few as possible)",
+            char_range('a', 'z').many(0, 0).lazy().explain_text()
+        );
+        assert_eq!(
+            "any character except a digit",
+            any_except(digit()).explain_text()
+        );
+        assert_eq!("optionally a tab", maybe(tab()).explain_text());
+        assert_eq!("'ab' (case insensitive)", text("ab").case_insensitive().explain_text());
+        assert_eq!(
+            "followed by 3 letters",
+            followed_by(letter().times(3)).explain_text()
+        );
+        assert_eq!(
+            "a letter (captured as 'l'), then the text captured as 'l'",
+            start_with(letter().named("l"))
+                .and_then(backref_name("l"))
+                .explain_text()
+        );
+    }
+}
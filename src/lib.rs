@@ -1,5 +1,18 @@
 mod builder;
+mod curated;
+mod describe;
+mod matching;
+mod normalize;
 mod parser;
+mod stack;
+mod syntax;
+mod tokens;
+mod validate;
 
 pub use builder::*;
-pub use parser::*;
\ No newline at end of file
+pub use curated::*;
+pub use matching::*;
+pub use parser::*;
+pub use stack::*;
+pub use tokens::*;
+pub use validate::*;
@@ -0,0 +1,293 @@
+//! Match text with patterns, using the regex crate
+
+use crate::builder::Pattern;
+use regex::{bytes, Captures, Error, Regex};
+use std::collections::HashMap;
+
+impl Pattern {
+    /// Named groups captured by the first match in the text.
+    /// Groups that did not participate in the match are absent.
+    /// None if the pattern does not compile or does not match
+    pub fn captures_named(&self, text: &str) -> Option<HashMap<String, String>> {
+        let re = self.compile().ok()?;
+        let caps = re.captures(text)?;
+        Some(
+            re.capture_names()
+                .flatten()
+                .filter_map(|n| caps.name(n).map(|m| (n.to_owned(), m.as_str().to_owned())))
+                .collect(),
+        )
+    }
+
+    /// Named groups captured by the first match in the text, converted into a record.
+    /// None if the pattern does not compile, does not match, or the conversion fails
+    pub fn captures_struct<T: FromCaptures>(&self, text: &str) -> Option<T> {
+        T::from_captures(self.captures_named(text)?)
+    }
+
+    /// Every value of the given named group, over all the matches in the text.
+    /// A repeated group only captures its last repetition in a match, so repeat the
+    /// whole pattern over the text instead: `digit().many(1, 0).named("n")` extracts
+    /// all the numbers. Empty if the pattern does not compile
+    pub fn extract_all_named(&self, text: &str, name: &str) -> Vec<String> {
+        match self.compile() {
+            Ok(re) => re
+                .captures_iter(text)
+                .filter_map(|caps| caps.name(name).map(|m| m.as_str().to_owned()))
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Compile the pattern into a regex. Fails with a syntax error if the pattern contains
+    /// lookarounds or backreferences, which the `regex` crate does not support: see `compile_fancy`,
+    /// or a pattern that `any_except` can't negate
+    pub fn compile(&self) -> Result<Regex, Error> {
+        self.check_backrefs()?;
+        self.check_negations()?;
+        Regex::new(&self.to_regex_string())
+    }
+
+    /// Compile the pattern into a regex matching bytes, for input that may not be valid UTF-8
+    pub fn compile_bytes(&self) -> Result<bytes::Regex, Error> {
+        self.check_backrefs()?;
+        self.check_negations()?;
+        bytes::Regex::new(&self.to_regex_string())
+    }
+
+    /// Fail on backreferences, that the `regex` crate reports as unrecognized escapes when
+    /// they use a name
+    fn check_backrefs(&self) -> Result<(), Error> {
+        match self.find_backref() {
+            Some(backref) => Err(Error::Syntax(format!(
+                "backreferences are not supported: {}",
+                backref
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// First backreference in the pattern
+    fn find_backref(&self) -> Option<&Pattern> {
+        match self {
+            Pattern::BackRef(..) => Some(self),
+            p => p.children().into_iter().find_map(Pattern::find_backref),
+        }
+    }
+
+    /// Fail on negated patterns that can't be negated, which render as nothing
+    fn check_negations(&self) -> Result<(), Error> {
+        match self.find_unsupported_negation() {
+            Some(exp) => Err(Error::Syntax(format!("pattern can't be negated: {}", exp))),
+            None => Ok(()),
+        }
+    }
+
+    /// First pattern given to `any_except` that can't be negated
+    fn find_unsupported_negation(&self) -> Option<&Pattern> {
+        match self {
+            Pattern::Not(exp) if !exp.can_negate() => Some(exp),
+            p => p
+                .children()
+                .into_iter()
+                .find_map(Pattern::find_unsupported_negation),
+        }
+    }
+
+    /// Make the preceding pattern an optional named group, used as a flag:
+    /// check whether it matched with `captures_named(..).contains_key(name)`
+    /// or `CapturesExt::has` on regex captures
+    pub fn flag_group(self, name: &str) -> Pattern {
+        self.named(name).many(0, 1)
+    }
+
+    /// Render the pattern for the `fancy-regex` crate, which accepts the same syntax as
+    /// `regex` plus lookaround and backreferences
+    pub fn to_string_fancy(&self) -> String {
+        self.to_regex_string()
+    }
+
+    /// Compile the pattern with the `fancy-regex` crate, available with the `fancy-regex` feature
+    #[cfg(feature = "fancy-regex")]
+    pub fn compile_fancy(&self) -> Result<fancy_regex::Regex, fancy_regex::Error> {
+        self.check_negations().map_err(fancy_regex::Error::InnerError)?;
+        fancy_regex::Regex::new(&self.to_string_fancy())
+    }
+
+    /// Make the preceding pattern an optional named group, returning the defaults
+    /// to apply to captures when the group is absent: an empty string
+    pub fn default_when_absent(self, name: &str) -> (Pattern, Defaults) {
+        let mut defaults = Defaults::default();
+        defaults.0.insert(name.to_owned(), String::new());
+        (self.named(name).many(0, 1), defaults)
+    }
+}
+
+/// Conversion from named captures, as returned by `captures_named`
+pub trait FromCaptures: Sized {
+    /// Build from the captures, None if a group is missing or invalid
+    fn from_captures(captures: HashMap<String, String>) -> Option<Self>;
+}
+
+/// Captures are kept as they are
+impl FromCaptures for HashMap<String, String> {
+    fn from_captures(captures: HashMap<String, String>) -> Option<Self> {
+        Some(captures)
+    }
+}
+
+/// Helpers on regex captures
+pub trait CapturesExt {
+    /// Did the named group participate in the match?
+    fn has(&self, name: &str) -> bool;
+}
+
+impl CapturesExt for Captures<'_> {
+    fn has(&self, name: &str) -> bool {
+        self.name(name).is_some()
+    }
+}
+
+/// Default values for optional named groups
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Defaults(HashMap<String, String>);
+
+impl Defaults {
+    /// Combine with other defaults
+    pub fn merge(mut self, other: Defaults) -> Self {
+        self.0.extend(other.0);
+        self
+    }
+
+    /// Fill the captures with the default of each absent group
+    pub fn apply(&self, mut captures: HashMap<String, String>) -> HashMap<String, String> {
+        for (name, value) in self.0.iter() {
+            captures
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        captures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::*;
+
+    #[test]
+    fn test_default_when_absent() {
+        let (p, defaults) = start_with(word_character().many(1, 0).named("key"))
+            .and_then("=")
+            .and_then(digit().many(1, 0))
+            .default_when_absent("value");
+        assert_eq!(r"(?P<key>\w+)=(?P<value>\d+)?", p.to_string());
+
+        let caps = p.captures_named("a=12").unwrap();
+        assert_eq!(Some("12"), caps.get("value").map(|s| s.as_str()));
+        let caps = p.captures_named("a=").unwrap();
+        assert_eq!(None, caps.get("value"));
+        let caps = defaults.apply(caps);
+        assert_eq!(Some("a"), caps.get("key").map(|s| s.as_str()));
+        assert_eq!(Some(""), caps.get("value").map(|s| s.as_str()));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Date {
+        year: u32,
+        month: u32,
+        day: u32,
+    }
+
+    impl FromCaptures for Date {
+        fn from_captures(captures: HashMap<String, String>) -> Option<Self> {
+            Some(Date {
+                year: captures.get("year")?.parse().ok()?,
+                month: captures.get("month")?.parse().ok()?,
+                day: captures.get("day")?.parse().ok()?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_captures_struct() {
+        let p = start_with(digit().times(4).named("year"))
+            .and_then("-")
+            .and_then(digit().times(2).named("month"))
+            .and_then("-")
+            .and_then(digit().times(2).named("day"));
+        assert_eq!(
+            Some(Date {
+                year: 2020,
+                month: 3,
+                day: 14
+            }),
+            p.captures_struct("on 2020-03-14")
+        );
+        assert_eq!(None, p.captures_struct::<Date>("on 2020-03"));
+        let caps: HashMap<String, String> = p.captures_struct("2020-03-14").unwrap();
+        assert_eq!(Some("03"), caps.get("month").map(|s| s.as_str()));
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_compile_fancy() {
+        let p = start_with(raw("(?<=\\$)")).and_then(digit().many(1, 0));
+        assert_eq!(r"(?<=\$)\d+", p.to_string_fancy());
+        assert!(Regex::new(&p.to_string()).is_err());
+        let re = p.compile_fancy().unwrap();
+        assert_eq!(
+            Some("42"),
+            re.find("42 or $42").unwrap().map(|m| m.as_str())
+        );
+        let re = start_with("a").and_then(followed_by("b")).compile_fancy().unwrap();
+        assert!(re.is_match("ab").unwrap());
+        assert!(!re.is_match("ac").unwrap());
+        let re = start_with(letter().named("l"))
+            .and_then(backref_name("l"))
+            .compile_fancy()
+            .unwrap();
+        assert!(re.is_match("aa").unwrap());
+        assert!(!re.is_match("ab").unwrap());
+        let re = start_with(digit().captured()).and_then(backref(1)).compile_fancy().unwrap();
+        assert!(re.is_match("11").unwrap());
+        assert!(!re.is_match("12").unwrap());
+        assert!(any_except(digit().many(1, 0)).compile_fancy().is_err());
+    }
+
+    #[test]
+    fn test_flag_group() {
+        let p = start_with(digit().many(1, 0).named("amount"))
+            .and_then(text("%").flag_group("percent"));
+        assert_eq!(r"(?P<amount>\d+)(?P<percent>%)?", p.to_string());
+        assert!(p.captures_named("50%").unwrap().contains_key("percent"));
+        assert!(!p.captures_named("50").unwrap().contains_key("percent"));
+
+        let re = Regex::new(&p.to_string()).unwrap();
+        assert!(re.captures("50%").unwrap().has("percent"));
+        assert!(!re.captures("50").unwrap().has("percent"));
+        assert!(re.captures("50").unwrap().has("amount"));
+    }
+
+    #[test]
+    fn test_compile() {
+        let re = start_with(digit().times(4).named("year"))
+            .and_then("-")
+            .and_then(digit().times(2).named("month"))
+            .compile()
+            .unwrap();
+        let caps = re.captures("on 2010-03").unwrap();
+        assert_eq!("2010", &caps["year"]);
+        assert_eq!("03", &caps["month"]);
+        assert!(raw("a(").compile().is_err());
+    }
+
+    #[test]
+    fn test_extract_all_named() {
+        let p = digit().many(1, 0).named("n");
+        assert_eq!(vec!["1", "2", "3"], p.extract_all_named("a1b2c3", "n"));
+        assert_eq!(vec!["12", "345"], p.extract_all_named("a12b345", "n"));
+        assert!(p.extract_all_named("abc", "n").is_empty());
+        assert!(p.extract_all_named("a1", "other").is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Simplify patterns without changing what they match
+
+use crate::builder::Pattern;
+
+impl Pattern {
+    /// Simplify the pattern and all its sub-patterns:
+    /// consecutive exact repetitions of the same pattern are merged, `a{2}a{3}` becoming `a{5}`,
+    /// consecutive texts are merged, repetitions of repetitions are collapsed when possible,
+    /// `(a+)*` becoming `a*`, and nested sequences and alternations are flattened.
+    /// The groups added implicitly when rendering a quantified pattern, like `(ab){2}` or
+    /// the outer group of `((ab)+)*`, may be merged or dropped, which renumbers the groups
+    /// after them: use named groups to refer to captures of a normalized pattern.
+    /// Repetitions containing explicit groups, from `captured` or `named`, are not merged,
+    /// and collapsing keeps them
+    pub fn normalize(self) -> Self {
+        match self.map(Pattern::normalize) {
+            Pattern::Sequence(exps) => {
+                let mut nexps: Vec<Pattern> = vec![];
+                let flat = exps.into_iter().flat_map(|p| match p {
+                    Pattern::Sequence(inner) => inner,
+                    p => vec![p],
+                });
+                for p in flat {
+                    match (p, nexps.last_mut()) {
+                        (Pattern::Text(t), Some(Pattern::Text(t0))) => t0.push_str(&t),
+                        (
+                            Pattern::Many {
+                                exp,
+                                low,
+                                high,
+                                lazy,
+                            },
+                            Some(Pattern::Many {
+                                exp: exp0,
+                                low: low0,
+                                high: high0,
+                                lazy: lazy0,
+                            }),
+                        ) if low == high
+                            && low0 == high0
+                            && *low0 > 0
+                            && low > 0
+                            && exp == *exp0
+                            && lazy == *lazy0
+                            && exp.count_capture_groups() == 0 =>
+                        {
+                            *low0 += low;
+                            *high0 += high;
+                        }
+                        (p, _) => nexps.push(p),
+                    }
+                }
+                if nexps.len() == 1 {
+                    nexps.pop().unwrap()
+                } else {
+                    Pattern::Sequence(nexps)
+                }
+            }
+            Pattern::Or(exps) => Pattern::Or(
+                exps.into_iter()
+                    .flat_map(|p| match p {
+                        Pattern::Or(inner) => inner,
+                        p => vec![p],
+                    })
+                    .collect(),
+            ),
+            Pattern::Many {
+                exp,
+                low,
+                high,
+                lazy,
+            } => match *exp {
+                Pattern::Many {
+                    exp: inner,
+                    low: low0,
+                    high: high0,
+                    lazy: lazy0,
+                } if lazy == lazy0 => match collapse((low0, high0), (low, high)) {
+                    Some((low, high)) => Pattern::Many {
+                        exp: inner,
+                        low,
+                        high,
+                        lazy,
+                    },
+                    None => Pattern::Many {
+                        exp: Box::new(Pattern::Many {
+                            exp: inner,
+                            low: low0,
+                            high: high0,
+                            lazy: lazy0,
+                        }),
+                        low,
+                        high,
+                        lazy,
+                    },
+                },
+                exp => Pattern::Many {
+                    exp: Box::new(exp),
+                    low,
+                    high,
+                    lazy,
+                },
+            },
+            p => p,
+        }
+    }
+}
+
+/// Bounds of a repetition of a repetition, when it can be written as a single repetition:
+/// exact counts multiply, and `?`, `*` and `+` combine into one of them
+fn collapse(inner: (u32, u32), outer: (u32, u32)) -> Option<(u32, u32)> {
+    let simple = |b: (u32, u32)| matches!(b, (0, 1) | (0, 0) | (1, 0));
+    match (inner, outer) {
+        ((l0, h0), (l, h)) if l0 == h0 && l == h && l0 > 0 && l > 0 => Some((l0 * l, l0 * l)),
+        (i, o) if simple(i) && simple(o) => Some(if i == o { i } else { (0, 0) }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::*;
+
+    #[test]
+    fn test_merge_repetitions() {
+        let p = text("a").times(2).and_then(text("a").times(3));
+        assert_eq!("a{2}a{3}", p.to_string());
+        assert_eq!("a{5}", p.normalize().to_string());
+
+        let p = start_with("x")
+            .and_then(digit().times(2))
+            .and_then(digit().times(2))
+            .and_then(digit().times(1))
+            .and_then(digit().many(1, 2));
+        assert_eq!(r"x\d{5}\d{1,2}", p.normalize().to_string());
+
+        let p = text("a").times(2).and_then(text("b").times(3));
+        assert_eq!("a{2}b{3}", p.normalize().to_string());
+        let p = either((digit().times(2).and_then(digit().times(2)), "b"));
+        assert_eq!(r"\d{4}|b", p.normalize().to_string());
+
+        // implicit groups are merged and renumbered
+        let p = text("ab").times(2).and_then(text("ab").times(3));
+        assert_eq!("(ab){2}(ab){3}", p.to_string());
+        assert_eq!(2, p.count_capture_groups());
+        let p = p.normalize();
+        assert_eq!("(ab){5}", p.to_string());
+        assert_eq!(1, p.count_capture_groups());
+        // explicit groups are kept
+        let p = digit().captured().times(2).and_then(digit().captured().times(2));
+        assert_eq!(r"(\d){2}(\d){2}", p.normalize().to_string());
+        let p = text("ab").named("x").times(2).and_then(text("ab").named("x").times(2));
+        assert_eq!(p.clone(), p.normalize());
+    }
+
+    #[test]
+    fn test_merge_texts() {
+        let p = Pattern::Sequence(vec![Pattern::Text("a".into()), Pattern::Text("b".into())]);
+        assert_eq!(Pattern::Text("ab".into()), p.normalize());
+        let p = Pattern::Sequence(vec![
+            text("a"),
+            Pattern::Sequence(vec![text("b"), digit(), Pattern::Sequence(vec![text("c")])]),
+            text("d"),
+        ]);
+        assert_eq!(
+            start_with("ab").and_then(digit()).and_then("cd"),
+            p.normalize()
+        );
+    }
+
+    #[test]
+    fn test_collapse_repetitions() {
+        let p = text("a").many(1, 0).many(0, 0);
+        assert_eq!("(a+)*", p.to_string());
+        assert_eq!("a*", p.normalize().to_string());
+        assert_eq!(
+            "a+",
+            text("a").many(1, 0).many(1, 0).normalize().to_string()
+        );
+        assert_eq!("a?", maybe(maybe("a")).normalize().to_string());
+        assert_eq!("a*", maybe(text("a").many(1, 0)).normalize().to_string());
+        assert_eq!(r"\d{6}", digit().times(2).times(3).normalize().to_string());
+        assert_eq!(
+            "(a{2})+",
+            text("a").times(2).many(1, 0).normalize().to_string()
+        );
+        assert_eq!(
+            "(a+){2}",
+            text("a").many(1, 0).times(2).normalize().to_string()
+        );
+        assert_eq!(
+            "(a+?)+",
+            text("a")
+                .many(1, 0)
+                .lazy()
+                .many(1, 0)
+                .normalize()
+                .to_string()
+        );
+
+        // the implicit group around the inner repetition is dropped, renumbering later groups
+        let p = text("ab").many(1, 0).many(0, 0).and_then(digit().captured());
+        assert_eq!(r"((ab)+)*(\d)", p.to_string());
+        assert_eq!(3, p.count_capture_groups());
+        let p = p.normalize();
+        assert_eq!(r"(ab)*(\d)", p.to_string());
+        assert_eq!(2, p.count_capture_groups());
+        // explicit groups are kept
+        let p = text("ab").captured().many(1, 0).many(0, 0).normalize();
+        assert_eq!("(ab)*", p.to_string());
+        assert_eq!(1, p.count_capture_groups());
+    }
+
+    #[test]
+    fn test_flatten_alternations() {
+        let nested = Pattern::Or(vec![
+            text("a"),
+            Pattern::Or(vec![text("b"), Pattern::Or(vec![text("c"), digit()])]),
+        ]);
+        assert_eq!(r"a|b|c|\d", nested.to_string());
+        assert_eq!(
+            r#"either(("a", "b", "c", digit()))"#,
+            nested.clone().normalize().to_code()
+        );
+        let p = start_with("x").and_then(nested.clone()).and_then("y");
+        assert_eq!(r"x(a|b|c|\d)y", p.to_string());
+        assert_eq!(r"x(a|b|c|\d)y", p.normalize().to_string());
+        let p = either((start_with("x").and_either(("a", "b")), "c"));
+        assert_eq!("x(a|b)|c", p.clone().normalize().to_string());
+        assert_eq!(p.clone(), p.normalize());
+    }
+}
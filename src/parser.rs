@@ -1,43 +1,357 @@
 //! Parse a regular expression into a pattern
 
-use crate::builder::Pattern;
+use crate::builder::{BackRefKind, ClassItem, Pattern};
+use crate::curated::{anything_including_newline, text_ci};
 use regex_syntax::ast::{
-    parse::Parser, Alternation, Assertion, AssertionKind, Ast, Class, ClassPerl, ClassPerlKind,
-    ClassUnicode, ClassUnicodeKind, Concat, Error, Group, GroupKind, Literal, Repetition, RepetitionKind,
-    RepetitionOp, RepetitionRange,
+    parse::Parser, Alternation, Assertion, AssertionKind, Ast, Class, ClassAscii, ClassAsciiKind,
+    ClassBracketed, ClassPerl, ClassPerlKind, ClassSet, ClassSetItem, ClassUnicode, ClassUnicodeKind, Concat, Error, ErrorKind, Flag, Flags, FlagsItemKind,
+    Group, GroupKind, HexLiteralKind, Literal, LiteralKind, Repetition, RepetitionKind, RepetitionOp,
+    RepetitionRange, SpecialLiteralKind,
 };
+use regex::Regex;
+use regex_syntax::hir::{self, Hir, HirKind};
+use std::fmt::{self, Display, Formatter};
+
+/// Flags given outside of the regular expression, like with `regex::RegexBuilder`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RegexFlags {
+    /// Match ignoring case
+    pub case_insensitive: bool,
+    /// `^` and `$` match at the start and end of lines
+    pub multi_line: bool,
+    /// `.` matches new lines
+    pub dot_matches_new_line: bool,
+}
 
 /// Explain a regex: turn it into a pattern
-pub fn explain(regex: &str) -> Result<Pattern, Error> {
-    let mut p = Parser::new();
-    p.parse(regex).and_then(|a| {
-        //println!("ast: {:?}", a);
-        do_explain(&a)
-    })
+///
+/// Lookarounds like `(?=a)` or `(?<=a)` and backreferences like `\1` or `\k<name>` are not
+/// supported by `regex_syntax`, they are parsed as named groups and turned back into lookarounds
+/// and backreferences. Other constructs that have no pattern are kept as raw fragments.
+/// Atomic groups like `(?>ab)` are not supported by `regex_syntax` and give an error.
+/// Possessive quantifiers are not supported either: `a++` is read as a nested repetition,
+/// like the `regex` crate does
+pub fn explain(regex: &str) -> Result<Pattern, ExplainError> {
+    let regex = mask_unsupported(regex);
+    let ast = Parser::new().parse(&regex).map_err(|e| match e.kind() {
+        ErrorKind::UnsupportedBackreference | ErrorKind::UnsupportedLookAround => {
+            ExplainError::Unsupported(regex[e.span().start.offset..e.span().end.offset].to_owned())
+        }
+        _ => ExplainError::Parse(Box::new(e)),
+    })?;
+    do_explain(&ast, &regex)
+}
+
+/// Prefix of the named groups standing for lookarounds
+const LOOKAROUND_PREFIX: &str = "__lookaround_";
+
+/// Prefix of the empty named groups standing for backreferences
+const BACKREF_PREFIX: &str = "__backref_";
+
+/// Lookaround openers, with the kind used in the group name
+const LOOKAROUNDS: [(&str, &str); 4] = [
+    ("(?=", "ahead"),
+    ("(?!", "not_ahead"),
+    ("(?<=", "behind"),
+    ("(?<!", "not_behind"),
+];
+
+/// Replace the lookaround openers and the backreferences by named groups, so `regex_syntax`
+/// can parse the regex: `(?=a)` becomes `(?P<__lookaround_ahead_0>a)`, `\1` becomes
+/// `(?P<__backref_1_1>)` and `\k<year>` becomes `(?P<__backref_2_year>)`
+fn mask_unsupported(regex: &str) -> String {
+    let chars: Vec<char> = regex.chars().collect();
+    let mut masked = String::with_capacity(regex.len());
+    let (mut i, mut class_depth, mut count) = (0, 0, 0);
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if class_depth == 0 && matches!(chars.get(i + 1), Some('1'..='9')) => {
+                let digits: String = chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                masked.push_str(&format!("(?P<{}{}_{}>)", BACKREF_PREFIX, count, digits));
+                count += 1;
+                i += 1 + digits.len();
+                continue;
+            }
+            '\\' if class_depth == 0 && chars.get(i + 1..i + 3) == Some(&['k', '<']) => {
+                let name: String = chars[i + 3..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .collect();
+                if !name.is_empty() && chars.get(i + 3 + name.len()) == Some(&'>') {
+                    masked.push_str(&format!("(?P<{}{}_{}>)", BACKREF_PREFIX, count, name));
+                    count += 1;
+                    i += 4 + name.len();
+                } else {
+                    masked.extend(&chars[i..i + 2]);
+                    i += 2;
+                }
+                continue;
+            }
+            '\\' => {
+                masked.extend(chars.get(i..i + 2).unwrap_or(&chars[i..]));
+                i += 2;
+                continue;
+            }
+            '[' => {
+                class_depth += 1;
+                masked.push('[');
+                i += 1;
+                // a closing bracket at the start of a class is a literal
+                if chars.get(i) == Some(&'^') {
+                    masked.push('^');
+                    i += 1;
+                }
+                if chars.get(i) == Some(&']') {
+                    masked.push(']');
+                    i += 1;
+                }
+                continue;
+            }
+            ']' if class_depth > 0 => class_depth -= 1,
+            '(' if class_depth == 0 => {
+                let rest: String = chars[i..].iter().take(4).collect();
+                if let Some((opener, kind)) = LOOKAROUNDS.iter().find(|(o, _)| rest.starts_with(o)) {
+                    masked.push_str(&format!("(?P<{}{}_{}>", LOOKAROUND_PREFIX, kind, count));
+                    count += 1;
+                    i += opener.len();
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        masked.push(chars[i]);
+        i += 1;
+    }
+    masked
+}
+
+/// Explain a regex meant to be compiled with the given flags, so the pattern carries them
+pub fn explain_with_flags(regex: &str, flags: RegexFlags) -> Result<Pattern, ExplainError> {
+    let mut p = explain(regex)?;
+    if flags.case_insensitive {
+        p = p.case_insensitive();
+    }
+    if flags.multi_line {
+        p = p.multiline();
+    }
+    if flags.dot_matches_new_line {
+        p = p.dotall();
+    }
+    Ok(p)
+}
+
+/// Explain a regex, then compile the rendered pattern, checking that it is equivalent
+/// to the original regex. Differences, like an extra capture group, give a `Mismatch` error
+pub fn explain_and_compile(regex: &str) -> Result<(Pattern, Regex), ExplainCompileError> {
+    let p = explain(regex).map_err(ExplainCompileError::Parse)?;
+    let rendered = p.to_regex_string();
+    let re = Regex::new(&rendered).map_err(ExplainCompileError::Compile)?;
+    let hir = |s: &str| regex_syntax::Parser::new().parse(s).ok().map(without_groups);
+    if hir(regex) != hir(&rendered) {
+        return Err(ExplainCompileError::Mismatch {
+            original: regex.to_owned(),
+            rendered,
+        });
+    }
+    Ok((p, re))
+}
+
+/// Remove the non capturing groups, which do not change what is matched, flattening
+/// the concatenations and alternations they contained
+fn without_groups(h: Hir) -> Hir {
+    let flatten = |hs: Vec<Hir>, alternation: bool| {
+        hs.into_iter()
+            .map(without_groups)
+            .flat_map(|h| match h.into_kind() {
+                HirKind::Concat(hs) if !alternation => hs,
+                HirKind::Alternation(hs) if alternation => hs,
+                kind => vec![from_kind(kind)],
+            })
+            .collect()
+    };
+    match h.into_kind() {
+        HirKind::Group(hir::Group {
+            kind: hir::GroupKind::NonCapturing,
+            hir,
+        }) => without_groups(*hir),
+        HirKind::Group(g) => Hir::group(hir::Group {
+            kind: g.kind,
+            hir: Box::new(without_groups(*g.hir)),
+        }),
+        HirKind::Repetition(r) => Hir::repetition(hir::Repetition {
+            hir: Box::new(without_groups(*r.hir)),
+            ..r
+        }),
+        HirKind::Concat(hs) => Hir::concat(flatten(hs, false)),
+        HirKind::Alternation(hs) => Hir::alternation(flatten(hs, true)),
+        kind => from_kind(kind),
+    }
 }
 
-/// Do the explaining
-fn do_explain(ast: &Ast) -> Result<Pattern, Error> {
+/// Rebuild a Hir from its kind
+fn from_kind(kind: HirKind) -> Hir {
+    match kind {
+        HirKind::Empty => Hir::empty(),
+        HirKind::Literal(l) => Hir::literal(l),
+        HirKind::Class(c) => Hir::class(c),
+        HirKind::Anchor(a) => Hir::anchor(a),
+        HirKind::WordBoundary(w) => Hir::word_boundary(w),
+        HirKind::Repetition(r) => Hir::repetition(r),
+        HirKind::Group(g) => Hir::group(g),
+        HirKind::Concat(hs) => Hir::concat(hs),
+        HirKind::Alternation(hs) => Hir::alternation(hs),
+    }
+}
+
+/// Failure of `explain`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExplainError {
+    /// The regex is invalid
+    Parse(Box<Error>),
+    /// The regex uses a construct that can't be turned into a pattern, like a backreference
+    /// in a class
+    Unsupported(String),
+}
+
+impl From<Error> for ExplainError {
+    fn from(e: Error) -> Self {
+        ExplainError::Parse(Box::new(e))
+    }
+}
+
+impl Display for ExplainError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplainError::Parse(e) => write!(f, "parse error: {}", e),
+            ExplainError::Unsupported(construct) => write!(f, "unsupported construct: {}", construct),
+        }
+    }
+}
+
+impl std::error::Error for ExplainError {}
+
+/// Failure of `explain_and_compile`
+#[derive(Debug)]
+pub enum ExplainCompileError {
+    /// The regex could not be explained
+    Parse(ExplainError),
+    /// The rendered pattern could not be compiled
+    Compile(regex::Error),
+    /// The rendered pattern is not equivalent to the original regex
+    Mismatch { original: String, rendered: String },
+}
+
+impl Display for ExplainCompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplainCompileError::Parse(e) => write!(f, "{}", e),
+            ExplainCompileError::Compile(e) => write!(f, "compile error: {}", e),
+            ExplainCompileError::Mismatch { original, rendered } => {
+                write!(f, "{:?} was rendered as {:?}", original, rendered)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExplainCompileError {}
+
+/// Do the explaining, the source is used to keep unsupported fragments as raw patterns
+fn do_explain(ast: &Ast, source: &str) -> Result<Pattern, ExplainError> {
     match ast {
-        Ast::Concat(Concat { asts, .. }) => Ok(simplify(
-            asts.iter()
-                .map(|a| do_explain(a))
-                .collect::<Result<Vec<Pattern>, Error>>()?,
-        )),
+        Ast::Concat(Concat { asts, .. }) => explain_concat(asts, source),
+        Ast::Literal(Literal {
+            c,
+            kind: LiteralKind::HexFixed(HexLiteralKind::X),
+            ..
+        }) if ('\u{1}'..='\u{1A}').contains(c) => Ok(Pattern::Control(*c)),
+        Ast::Literal(Literal {
+            c,
+            kind: LiteralKind::Special(SpecialLiteralKind::Tab),
+            ..
+        })
+        | Ast::Literal(Literal {
+            c,
+            kind: LiteralKind::Special(SpecialLiteralKind::LineFeed),
+            ..
+        })
+        | Ast::Literal(Literal {
+            c,
+            kind: LiteralKind::Special(SpecialLiteralKind::CarriageReturn),
+            ..
+        }) => Ok(Pattern::Control(*c)),
+        Ast::Literal(Literal {
+            c,
+            kind: LiteralKind::HexFixed(..),
+            ..
+        })
+        | Ast::Literal(Literal {
+            c,
+            kind: LiteralKind::HexBrace(..),
+            ..
+        }) => Ok(Pattern::HexChar(*c as u32)),
         Ast::Literal(Literal { c, .. }) => Ok(Pattern::Text(format!("{}", c))),
         Ast::Alternation(Alternation { asts, .. }) => Ok(Pattern::Or(
             asts.iter()
-                .map(|a| do_explain(a))
-                .collect::<Result<Vec<Pattern>, Error>>()?,
+                .map(|a| do_explain(a, source))
+                .collect::<Result<Vec<Pattern>, ExplainError>>()?,
         )),
-        Ast::Group(Group { ast, kind:GroupKind::CaptureName(n),.. }) => do_explain(ast).map(|p| Pattern::Named{exp:Box::new(p),name:n.name.clone()}),
-        Ast::Group(Group { ast, .. }) => do_explain(ast),
-        Ast::Repetition(Repetition { ast, op, .. }) => {
+        Ast::Group(Group { ast, kind: GroupKind::CaptureName(n), .. })
+            if n.name.starts_with(LOOKAROUND_PREFIX) =>
+        {
+            let kind = &n.name[LOOKAROUND_PREFIX.len()..];
+            let exp = Box::new(do_explain(ast, source)?);
+            let negated = kind.starts_with("not_");
+            Ok(if kind.contains("behind") {
+                Pattern::LookBehind { exp, negated }
+            } else {
+                Pattern::LookAhead { exp, negated }
+            })
+        }
+        Ast::Group(Group { kind: GroupKind::CaptureName(n), .. })
+            if n.name.starts_with(BACKREF_PREFIX) =>
+        {
+            let (_, reference) = n.name[BACKREF_PREFIX.len()..]
+                .split_once('_')
+                .unwrap_or_default();
+            Ok(Pattern::BackRef(match reference.parse() {
+                Ok(index) => BackRefKind::Index(index),
+                Err(_) => BackRefKind::Name(reference.to_owned()),
+            }))
+        }
+        Ast::Group(Group { ast, kind:GroupKind::CaptureName(n),.. }) => do_explain(ast, source).map(|p| Pattern::Named{exp:Box::new(p),name:n.name.clone()}),
+        Ast::Group(Group { ast, kind:GroupKind::CaptureIndex(_),.. }) => do_explain(ast, source).map(|p| Pattern::Group{exp:Box::new(p),capturing:true}),
+        Ast::Group(Group { ast, kind: GroupKind::NonCapturing(flags), .. })
+            if matches!(flags.items.as_slice(), [i] if i.kind == FlagsItemKind::Flag(Flag::CaseInsensitive)) =>
+        {
+            match do_explain(ast, source)? {
+                Pattern::Text(t) => Ok(text_ci(&t)),
+                p => Ok(with_flags(flags, p)),
+            }
+        }
+        Ast::Group(Group { ast, kind: GroupKind::NonCapturing(flags), .. }) if flags.items.is_empty() => {
+            do_explain(ast, source).map(|p| Pattern::Group{exp:Box::new(p),capturing:false})
+        }
+        Ast::Group(Group { ast, kind: GroupKind::NonCapturing(flags), .. }) => {
+            Ok(with_flags(flags, do_explain(ast, source)?))
+        }
+        Ast::Repetition(Repetition { ast, op, greedy, .. }) => {
             let bds = bounds(op);
+            // a quantified alternation is always rendered in a non capturing group
+            let exp = match do_explain(ast, source)? {
+                Pattern::Group {
+                    exp,
+                    capturing: false,
+                } if matches!(*exp, Pattern::Or(..)) => *exp,
+                exp => exp,
+            };
             Ok(Pattern::Many {
-                exp: Box::new(do_explain(ast)?),
+                exp: Box::new(exp),
                 low: bds.0,
                 high: bds.1,
+                lazy: !greedy,
             })
         }
         Ast::Class(Class::Perl(ClassPerl {
@@ -60,6 +374,16 @@ fn do_explain(ast: &Ast) -> Result<Pattern, Error> {
             negated:true,
             ..
         })) => Ok(Pattern::Not(Box::new(Pattern::WordCharacter))),
+        Ast::Class(Class::Perl(ClassPerl {
+            kind: ClassPerlKind::Space,
+            negated:false,
+            ..
+        })) => Ok(Pattern::Whitespace),
+        Ast::Class(Class::Perl(ClassPerl {
+            kind: ClassPerlKind::Space,
+            negated:true,
+            ..
+        })) => Ok(Pattern::Not(Box::new(Pattern::Whitespace))),
         Ast::Assertion(Assertion {
             kind: AssertionKind::StartLine,
             ..
@@ -68,18 +392,211 @@ fn do_explain(ast: &Ast) -> Result<Pattern, Error> {
             kind: AssertionKind::EndLine,
             ..
         }) => Ok(Pattern::InputEnd),
+        Ast::Assertion(Assertion {
+            kind: AssertionKind::StartText,
+            ..
+        }) => Ok(Pattern::TextStart),
+        Ast::Assertion(Assertion {
+            kind: AssertionKind::EndText,
+            ..
+        }) => Ok(Pattern::TextEnd),
+        Ast::Assertion(Assertion {
+            kind: AssertionKind::WordBoundary,
+            ..
+        }) => Ok(Pattern::WordBoundary { negated: false }),
+        Ast::Assertion(Assertion {
+            kind: AssertionKind::NotWordBoundary,
+            ..
+        }) => Ok(Pattern::WordBoundary { negated: true }),
         Ast::Class(Class::Unicode(ClassUnicode {
             kind: ClassUnicodeKind::OneLetter(c),
             negated: false,
             ..
-        })) if *c == 'N' => Ok(Pattern::Letter),
+        })) if *c == 'L' => Ok(Pattern::Letter),
         Ast::Class(Class::Unicode(ClassUnicode {
             kind: ClassUnicodeKind::OneLetter(c),
             negated: true,
             ..
-        })) if *c == 'N' => Ok(Pattern::Not(Box::new(Pattern::Letter))),
+        })) if *c == 'L' => Ok(Pattern::Not(Box::new(Pattern::Letter))),
+        Ast::Class(Class::Unicode(ClassUnicode {
+            kind: ClassUnicodeKind::OneLetter(c),
+            negated: false,
+            ..
+        })) if *c == 'N' => Ok(Pattern::Number),
+        Ast::Class(Class::Unicode(ClassUnicode {
+            kind: ClassUnicodeKind::OneLetter(c),
+            negated: true,
+            ..
+        })) if *c == 'N' => Ok(Pattern::Not(Box::new(Pattern::Number))),
+        Ast::Class(Class::Unicode(ClassUnicode {
+            kind: ClassUnicodeKind::OneLetter(c),
+            negated,
+            ..
+        })) => Ok(Pattern::UnicodeClass {
+            name: c.to_string(),
+            negated: *negated,
+        }),
+        Ast::Class(Class::Unicode(ClassUnicode {
+            kind: ClassUnicodeKind::Named(name),
+            negated,
+            ..
+        })) => Ok(match name.strip_prefix('^') {
+            Some(name) => Pattern::UnicodeClass {
+                name: name.to_owned(),
+                negated: !*negated,
+            },
+            None => Pattern::UnicodeClass {
+                name: name.clone(),
+                negated: *negated,
+            },
+        }),
         Ast::Dot{..} => Ok(Pattern::Any),
-        _ => Ok(Pattern::Raw(String::new())),
+        Ast::Class(Class::Bracketed(c)) if is_any_character(c) => Ok(anything_including_newline()),
+        Ast::Class(Class::Bracketed(ClassBracketed {
+            kind: ClassSet::Item(ClassSetItem::Ascii(ClassAscii { kind, negated, .. })),
+            negated: class_negated,
+            ..
+        })) => {
+            let p = Pattern::PosixClass(posix_name(kind).to_owned());
+            if negated != class_negated {
+                Ok(Pattern::Not(Box::new(p)))
+            } else {
+                Ok(p)
+            }
+        }
+        Ast::Class(Class::Bracketed(ClassBracketed {
+            kind: ClassSet::Item(ClassSetItem::Range(r)),
+            negated,
+            ..
+        })) => {
+            let p = Pattern::CharRange {
+                from: r.start.c,
+                to: r.end.c,
+            };
+            if *negated {
+                Ok(Pattern::Not(Box::new(p)))
+            } else {
+                Ok(p)
+            }
+        }
+        Ast::Class(Class::Bracketed(ClassBracketed { kind, negated, .. }))
+            if class_set_items(kind).is_some() =>
+        {
+            Ok(Pattern::CharSet {
+                items: class_set_items(kind).unwrap_or_default(),
+                negated: *negated,
+            })
+        }
+        Ast::Class(Class::Bracketed(ClassBracketed { span, .. })) => {
+            Ok(Pattern::Raw(source[span.start.offset..span.end.offset].to_owned()))
+        }
+        Ast::Empty(..) | Ast::Flags(..) => Ok(Pattern::Raw(String::new())),
+        // keep what has no pattern yet as is, so nothing is lost
+        _ => {
+            let span = ast.span();
+            Ok(Pattern::Raw(source[span.start.offset..span.end.offset].to_owned()))
+        }
+    }
+}
+
+/// Apply the flags to the pattern, the ones after `-` being turned off. `x` is ignored,
+/// since it only changes how the regex is parsed
+fn with_flags(flags: &Flags, exp: Pattern) -> Pattern {
+    let (mut on, mut off) = (String::new(), String::new());
+    let mut negated = false;
+    for item in &flags.items {
+        let c = match &item.kind {
+            FlagsItemKind::Negation => {
+                negated = true;
+                continue;
+            }
+            FlagsItemKind::Flag(Flag::CaseInsensitive) => 'i',
+            FlagsItemKind::Flag(Flag::MultiLine) => 'm',
+            FlagsItemKind::Flag(Flag::DotMatchesNewLine) => 's',
+            FlagsItemKind::Flag(Flag::SwapGreed) => 'U',
+            FlagsItemKind::Flag(Flag::Unicode) => 'u',
+            FlagsItemKind::Flag(Flag::IgnoreWhitespace) => continue,
+        };
+        if negated {
+            off.push(c);
+        } else {
+            on.push(c);
+        }
+    }
+    let exp = if on.is_empty() { exp } else { exp.flags(&on) };
+    if off.is_empty() {
+        exp
+    } else {
+        exp.without_flags(&off)
+    }
+}
+
+/// Explain a concatenation: flags set inline, like `(?i)`, apply to the rest of it
+fn explain_concat(asts: &[Ast], source: &str) -> Result<Pattern, ExplainError> {
+    match asts.iter().position(|a| matches!(a, Ast::Flags(..))) {
+        Some(pos) => {
+            let mut exps = asts[..pos]
+                .iter()
+                .map(|a| do_explain(a, source))
+                .collect::<Result<Vec<Pattern>, ExplainError>>()?;
+            if let Ast::Flags(set) = &asts[pos] {
+                let rest = explain_concat(&asts[pos + 1..], source)?;
+                exps.push(with_flags(&set.flags, rest));
+            }
+            Ok(simplify(exps))
+        }
+        None => Ok(simplify(
+            asts.iter()
+                .map(|a| do_explain(a, source))
+                .collect::<Result<Vec<Pattern>, ExplainError>>()?,
+        )),
+    }
+}
+
+/// Is the class a shorthand and its negation, like `[\s\S]`, matching any character
+fn is_any_character(class: &ClassBracketed) -> bool {
+    match &class.kind {
+        ClassSet::Item(ClassSetItem::Union(u)) if !class.negated => match u.items.as_slice() {
+            [ClassSetItem::Perl(p1), ClassSetItem::Perl(p2)] => {
+                p1.kind == p2.kind && p1.negated != p2.negated
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Characters and ranges of a class made only of literals and ranges, like `[a-z0-9_]`
+fn class_set_items(set: &ClassSet) -> Option<Vec<ClassItem>> {
+    let item = |i: &ClassSetItem| match i {
+        ClassSetItem::Literal(l) => Some(ClassItem::Char(l.c)),
+        ClassSetItem::Range(r) => Some(ClassItem::Range(r.start.c, r.end.c)),
+        _ => None,
+    };
+    match set {
+        ClassSet::Item(ClassSetItem::Union(u)) => u.items.iter().map(item).collect(),
+        ClassSet::Item(i) => item(i).map(|i| vec![i]),
+        _ => None,
+    }
+}
+
+/// Name of a POSIX class
+fn posix_name(kind: &ClassAsciiKind) -> &'static str {
+    match kind {
+        ClassAsciiKind::Alnum => "alnum",
+        ClassAsciiKind::Alpha => "alpha",
+        ClassAsciiKind::Ascii => "ascii",
+        ClassAsciiKind::Blank => "blank",
+        ClassAsciiKind::Cntrl => "cntrl",
+        ClassAsciiKind::Digit => "digit",
+        ClassAsciiKind::Graph => "graph",
+        ClassAsciiKind::Lower => "lower",
+        ClassAsciiKind::Print => "print",
+        ClassAsciiKind::Punct => "punct",
+        ClassAsciiKind::Space => "space",
+        ClassAsciiKind::Upper => "upper",
+        ClassAsciiKind::Word => "word",
+        ClassAsciiKind::Xdigit => "xdigit",
     }
 }
 
@@ -97,34 +614,33 @@ fn bounds(op: &RepetitionOp) -> (u32, u32) {
     }
 }
 
-/// Simplify a list of patterns
+/// Simplify a list of patterns: merge consecutive texts, drop empty raw patterns,
+/// keep everything else in order
 fn simplify(exps: Vec<Pattern>) -> Pattern {
     let mut nexps = vec![];
     for p in exps.into_iter() {
-        if let Pattern::Text(t) = p {
-            let op0 = nexps.pop();
-            if let Some(Pattern::Text(mut t0)) = op0 {
-                t0.push_str(&t);
-                nexps.push(Pattern::Text(t0));
-            } else {
-                if let Some(p0) = op0 {
-                    nexps.push(p0);
-                }
-                nexps.push(Pattern::Text(t));
-            }
-        } else {
-            if matches!(&p, Pattern::Raw(s) if s.is_empty()){
-                // ignore
-            } else {
-                nexps.push(p);
-            }
-            
+        match (p, nexps.last_mut()) {
+            (Pattern::Text(t), Some(Pattern::Text(t0))) => t0.push_str(&t),
+            (Pattern::Raw(s), _) if s.is_empty() => {}
+            (p, _) => nexps.push(p),
         }
     }
     if nexps.len() == 1 {
         nexps.pop().unwrap()
     } else {
-        Pattern::Sequence(nexps)
+        // alternatives in a sequence are rendered in a capture group already
+        Pattern::Sequence(
+            nexps
+                .into_iter()
+                .map(|p| match p {
+                    Pattern::Group {
+                        exp,
+                        capturing: true,
+                    } if matches!(*exp, Pattern::Or(..)) => *exp,
+                    p => p,
+                })
+                .collect(),
+        )
     }
 }
 
@@ -177,19 +693,24 @@ impl ExplainState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::builder::ToCode;
+    use crate::builder::*;
 
     #[test]
     fn test_basic_explain() {
         assert_explain(r#"text("Handel")"#,"Handel");
         assert_explain(r#"word_character()"#,r"\w");
-        assert_explain(r#"letter()"#,r"\pN");
+        assert_explain(r#"whitespace()"#,r"\s");
+        assert_explain(r#"any_except(whitespace())"#,r"\S");
+        assert_explain(r#"letter()"#,r"\pL");
+        assert_explain(r#"number().and_then(any_except(number()))"#,r"\pN\PN");
         assert_explain(r#"either(("gray", "grey"))"#,"gray|grey");
         assert_explain(r#"start_with("gr").and_either(("a", "e")).and_then("y")"#,"gr(a|e)y");
         assert_explain(r#"start_with("colo").and_maybe("u").and_then("r")"#,"colou?r");
         assert_explain(r#"digit().many(2, 3)"#,r#"\d{2,3}"#);
+        assert_explain(r#"digit().at_least(3)"#,r#"\d{3,}"#);
+        assert_explain(r#"digit().at_most(2)"#,r#"\d{0,2}"#);
         assert_explain(r#"at_start().and_then(digit()).times(4).and_then("-").and_then(digit()).times(2).and_then("-").and_then(digit()).times(2).must_end()"#,r"^\d{4}-\d{2}-\d{2}$");
-        assert_explain(r#"any_except(digit()).and_then(any_except(letter())).and_then(any_except(word_character()))"#,r#"\D\PN\W"#);
+        assert_explain(r#"any_except(digit()).and_then(any_except(letter())).and_then(any_except(word_character()))"#,r#"\D\PL\W"#);
         assert_explain(r#"start_with(digit().times(4).named("y")).and_then("-").and_then(digit().times(2).named("m")).and_then("-").and_then(digit().times(2).named("d"))"#,r#"(?x)
     (?P<y>\d{4})  # the year
     -
@@ -197,6 +718,342 @@ mod tests {
     -
     (?P<d>\d{2})   # the day
     "#);
+        assert_explain(r#"start_with("a").and_then(control_char('A'))"#,r"a\x01");
+        assert_explain(r#"maybe("a").and_then("bc")"#,"a?bc");
+        assert_explain(r#"digit().times(4).captured()"#,r"(\d{4})");
+        assert_explain(r#"text("ab").captured().one_or_more()"#,"(ab)+");
+    }
+
+    #[test]
+    fn test_explain_mixed() {
+        assert_explain(r#"digit().and_then("-").and_then(digit()).and_then("-").and_then(digit())"#,r"\d-\d-\d");
+        assert_explain(r#"start_with("a").and_then(digit()).and_then("bc").and_then(digit()).and_then("d")"#,r"a\dbc\dd");
+        assert_eq!(r"\d-\d-\d", explain(r"\d-\d-\d").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_anchor_then_group() {
+        let regex = r"^(?P<y>\d{4})-$";
+        assert_explain(r#"at_start().and_then(digit().times(4).named("y")).and_then("-").must_end()"#,regex);
+        let p = at_start().and_then(digit().times(4).named("y")).and_then("-").must_end();
+        assert_eq!(regex, p.to_string());
+        let re = regex::Regex::new(&p.to_string()).unwrap();
+        assert_eq!("2020", &re.captures("2020-").unwrap()["y"]);
+        assert!(!re.is_match("2020-01"));
+    }
+
+    #[test]
+    fn test_explain_shorthand_classes() {
+        assert_explain(r#"anything_including_newline()"#, r"[\s\S]");
+        assert_explain(r#"anything_including_newline()"#, r"[\D\d]");
+        assert_explain(r#"start_with("a").and_then(anything_including_newline()).and_then("b")"#, r"a[\s\S]b");
+        assert_explain(r#"raw("[\\w\\d]")"#, r"[\w\d]");
+    }
+
+    #[test]
+    fn test_explain_posix_class() {
+        assert_explain(r#"posix_class("alpha")"#, "[[:alpha:]]");
+        assert_explain(r#"posix_class("digit")"#, "[[:digit:]]");
+        assert_explain(r#"any_except(posix_class("alpha"))"#, "[[:^alpha:]]");
+        assert_explain(r#"any_except(posix_class("alpha"))"#, "[^[:alpha:]]");
+        assert_explain(r#"posix_class("alpha").and_many(posix_class("digit"))"#, "[[:alpha:]][[:digit:]]+");
+        assert_explain(r#"raw("[[:alpha:]0-9]")"#, "[[:alpha:]0-9]");
+        for regex in &["[[:alpha:]]", "[[:^space:]]", "[[:alpha:]0-9]"] {
+            assert_eq!(*regex, explain(regex).unwrap().to_string());
+        }
+        let (p, re) = explain_and_compile("[[:digit:]]+").unwrap();
+        assert_eq!(posix_class("digit").one_or_more(), p);
+        assert_eq!("[[:digit:]]+", p.to_regex_string());
+        assert!(re.is_match("42"));
+        assert!(!re.is_match("a"));
+        for name in &["alnum", "blank", "cntrl", "punct", "upper", "xdigit"] {
+            let regex = format!("[[:{}:]]", name);
+            assert_eq!(posix_class(name), explain(&regex).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_explain_text_ci() {
+        assert_explain(r#"text_ci("abc")"#, "(?i:abc)");
+        assert_explain(r#"start_with("id=").and_then(text_ci("abc"))"#, "id=(?i:abc)");
+        assert_eq!("id=(?i:abc)", explain("id=(?i:abc)").unwrap().to_string());
+        assert_explain(r#"digit().case_insensitive()"#, r"(?i:\d)");
+    }
+
+    #[test]
+    fn test_explain_with_flags() {
+        let flags = RegexFlags {
+            case_insensitive: true,
+            ..RegexFlags::default()
+        };
+        let p = explain_with_flags(r"^abc\d$", flags).unwrap();
+        assert_eq!(
+            r#"at_start().and_then("abc").and_then(digit()).must_end().case_insensitive()"#,
+            p.to_code()
+        );
+        assert_eq!(r"(?i:^abc\d$)", p.to_string());
+        assert!(regex::Regex::new(&p.to_string()).unwrap().is_match("ABC1"));
+        assert_eq!(
+            explain("abc").unwrap().to_code(),
+            explain_with_flags("abc", RegexFlags::default()).unwrap().to_code()
+        );
+        let flags = RegexFlags {
+            multi_line: true,
+            dot_matches_new_line: true,
+            ..RegexFlags::default()
+        };
+        assert_eq!("(?ms:^.$)", explain_with_flags("^.$", flags).unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_char_range() {
+        assert_explain("char_range('a', 'z')", "[a-z]");
+        assert_explain("char_range('a', 'z').one_or_more()", "[a-z]+");
+        assert_explain(r#"start_with("x").and_many(char_range('0', '9'))"#, "x[0-9]+");
+        assert_explain("any_except(char_range('a', 'z'))", "[^a-z]");
+        for regex in &["[a-z]+", r"[\--\]]{2,3}", "[à-ÿ]*", "[^0-9]"] {
+            assert_eq!(*regex, explain(regex).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn test_explain_mixed_class() {
+        assert_explain("char_class().range('a', 'z').range('0', '9').chars(['_'])", "[a-z0-9_]");
+        assert_explain(
+            "any_except(char_class().range('a', 'z').range('0', '9').chars(['_']))",
+            "[^a-z0-9_]",
+        );
+        assert_explain("char_class().range('a', 'c').chars(['x', 'y', 'z'])", "[a-cxyz]");
+        assert_explain(
+            r#"start_with("x").and_then(char_class().chars(['-', ']']).range('a', 'f')).and_then("y")"#,
+            r"x[\-\]a-f]y",
+        );
+        assert_explain(
+            "char_class().range('à', 'ÿ').range('A', 'Z').one_or_more()",
+            "[à-ÿA-Z]+",
+        );
+        assert_explain("one_of(['a', 'b'])", "[ab]");
+        assert_explain(r#"raw("[\\w-]")"#, r"[\w-]");
+        for regex in &["[a-z0-9_]", "x[a-z_]+y", r"[\-\]a-f]{2}", "[^à-ÿA-Z]*", "(?:[a-c]|x)+"] {
+            let (p, _) = explain_and_compile(regex).unwrap();
+            assert_eq!(*regex, p.to_regex_string());
+        }
+    }
+
+    #[test]
+    fn test_explain_quantified_alternation() {
+        assert_explain(r#"either(("a", "b")).many(2, 4)"#, "(?:a|b){2,4}");
+        assert_eq!("(?:a|b){2,4}", explain("(?:a|b){2,4}").unwrap().to_string());
+        assert_eq!("(a|b){2,4}", explain("(a|b){2,4}").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_escaped_text() {
+        assert_explain(r#"text("a.b+c")"#, r"a\.b\+c");
+        assert_explain(r#"text("C:\\dir")"#, r"C:\\dir");
+        assert_eq!(r"a\.b\+c", explain(r"a\.b\+c").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_lazy() {
+        assert_explain("digit().and_many(digit()).lazy()", r"\d\d+?");
+        assert_explain(r#"start_with("<").and_maybe_many(word_character()).lazy().and_then(">")"#, r"<\w*?>");
+        assert_explain("digit().many(2, 3).lazy()", r"\d{2,3}?");
+        for regex in &[r"a??b", r"\d{2,}?", r"(ab)*?c"] {
+            assert_eq!(*regex, explain(regex).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn test_explain_one_of() {
+        assert_explain("one_of(['a', 'b', 'c'])", "[abc]");
+        assert_explain("any_except(one_of(['a', 'b']))", "[^ab]");
+        assert_explain(r#"start_with("x").and_many(one_of(['-', '+']))"#, r"x[\-+]+");
+        for regex in &["[abc]", "[^ab]{2}", r"[\]\^\\\-]"] {
+            assert_eq!(*regex, explain(regex).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn test_explain_atomic_possessive() {
+        // regex_syntax does not support atomic groups
+        assert!(matches!(explain("(?>ab)"), Err(ExplainError::Parse(..))));
+        // possessive quantifiers are parsed as nested repetitions
+        let p = explain("a++").unwrap();
+        let re = regex::Regex::new(&p.to_string()).unwrap();
+        assert!(re.is_match("aaa"));
+        assert_eq!(
+            regex::Regex::new("a++").unwrap().find("baab").map(|m| m.as_str()),
+            re.find("baab").map(|m| m.as_str())
+        );
+    }
+
+    #[test]
+    fn test_explain_word_boundary() {
+        assert_explain(
+            r#"word_boundary().and_then("cat").and_then(word_boundary())"#,
+            r"\bcat\b",
+        );
+        assert_explain(r#"start_with("a").and_then(not_word_boundary())"#, r"a\B");
+    }
+
+    #[test]
+    fn test_explain_and_compile() {
+        let s = r"(?x)
+        (?P<year>\d{4})  # the year
+        -
+        (?P<month>\d{2}) # the month
+        -
+        (?P<day>\d{2})   # the day
+        ";
+        let (p, re) = explain_and_compile(s).unwrap();
+        assert_eq!(r"(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})", p.to_string());
+        let original = Regex::new(s).unwrap();
+        for text in &["2010-03-14", "on 1999-12-31!", "2010-3-14"] {
+            assert_eq!(
+                original.captures(text).map(|c| c["month"].to_owned()),
+                re.captures(text).map(|c| c["month"].to_owned())
+            );
+        }
+
+        assert!(matches!(
+            explain_and_compile("a++"),
+            Err(ExplainCompileError::Mismatch { rendered, .. }) if rendered == "(a+)+"
+        ));
+        assert!(matches!(
+            explain_and_compile("a("),
+            Err(ExplainCompileError::Parse(..))
+        ));
+    }
+
+    #[test]
+    fn test_explain_named() {
+        assert_explain(r#"digit().times(4).captured()"#, r"(\d{4})");
+        assert_explain(r#"digit().times(2).named("y")"#, r"(?P<y>\d{2})");
+        assert_explain(r#"digit().times(2).captured()"#, r"(\d{2})");
+        assert_explain(r#"group(digit().times(2))"#, r"(?:\d{2})");
+    }
+
+    #[test]
+    fn test_explain_lookahead() {
+        assert_explain(r#"start_with("a").and_then(followed_by("b"))"#, "a(?=b)");
+        assert_explain(r#"start_with("a").and_then(not_followed_by(digit()))"#, r"a(?!\d)");
+        let password = r"^(?=.*\d)(?=.*[a-z])(?!.*\s).{8,}$";
+        assert_eq!(password, explain(password).unwrap().to_string());
+        for regex in &[r"\(?=a\)", r"(a)(?P<b>c)"] {
+            assert_eq!(*regex, explain(regex).unwrap().to_string());
+        }
+        // no lookahead in classes
+        assert_eq!(r"[\(\?=]a", explain("[(?=]a").unwrap().to_string());
+        assert_eq!(r"[\]\(\)]", explain("[]()]").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_lookbehind() {
+        assert_explain(r#"preceded_by("a").and_then("b")"#, "(?<=a)b");
+        assert_explain(r#"not_preceded_by(digit()).and_then("b")"#, r"(?<!\d)b");
+        assert_eq!(r"(?<=\$)\d+(?!\.)", explain(r"(?<=\$)\d+(?!\.)").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_text_anchors() {
+        assert_explain(r#"at_text_start().and_then("a").must_end_text()"#, r"\Aa\z");
+        assert_explain(r#"at_start().and_then("a").must_end()"#, r"^a$");
+    }
+
+    #[test]
+    fn test_explain_unicode_classes() {
+        assert_explain(r#"unicode_category("Greek")"#, r"\p{Greek}");
+        assert_explain(r#"any_except(unicode_category("Lu"))"#, r"\P{Lu}");
+        assert_explain(r#"any_except(unicode_category("Greek"))"#, r"\p{^Greek}");
+        assert_explain(r#"unicode_category("P").one_or_more()"#, r"\pP+");
+        assert_explain("letter()", r"\pL");
+        let code = explain(r"a\p{Greek}b").unwrap().to_code();
+        assert_eq!(r#"start_with("a").and_then(unicode_category("Greek")).and_then("b")"#, code);
+    }
+
+    #[test]
+    fn test_explain_hex_char() {
+        assert_explain("hex_char(0x41)", r"\x41");
+        assert_explain("hex_char(0x41)", r"\x{41}");
+        assert_explain("hex_char(0x1F600)", r"\x{1F600}");
+        assert_explain(r#"start_with("a").and_then(hex_char(0xE9))"#, r"a\u00E9");
+        assert_eq!(r"\x{1F600}+", explain(r"\x{1F600}+").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_line_characters() {
+        assert_explain("tab().and_then(carriage_return()).and_then(newline())", r"\t\r\n");
+        assert_explain("tab()", r"\x09");
+        assert_eq!(r"a\r?\n", explain(r"a\r?\n").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_explain_flags() {
+        assert_explain(r#"text_ci("ab")"#, "(?i:ab)");
+        assert_explain(r#"start_with("a").and_then(digit().dotall().case_insensitive())"#, r"a(?si:\d)");
+        assert_explain(r#"start_with("a").and_then(text("bc").case_insensitive())"#, "a(?i)bc");
+        assert_explain(r#"start_with("a").and_then(digit().multiline())"#, r"a(?mx: \d )");
+        assert_explain(r#"digit().multiline().dotall().flags("U")"#, r"(?msU:\d)");
+        assert_explain(r#"digit().without_flags("i")"#, r"(?-i:\d)");
+        let (p, re) = explain_and_compile("(?m)^a$").unwrap();
+        assert_eq!("(?m:^a$)", p.to_string());
+        assert!(re.is_match("b\na"));
+    }
+
+    #[test]
+    fn test_explain_negated_flags() {
+        assert_explain(r#"text("a").case_insensitive().without_flags("s")"#, "(?i-s:a)");
+        assert_explain(
+            r#"start_with("a").and_then(start_with("b").and_then(text("c").without_flags("i")).case_insensitive())"#,
+            "a(?i)b(?-i)c",
+        );
+        assert_eq!("(?i:a(?-i:b))", explain("(?i)a(?-i:b)").unwrap().to_string());
+        assert_eq!("(?-i:c)", explain("(?i)(?-i)c").unwrap().to_string());
+        let inputs = ["a", "A", "ab", "aB", "Ab", "abc", "aBc", "aBC", "ABC", "a\nb", "A\nb"];
+        for regex in &["(?i-s:a)", "a(?i)b(?-i)c", "(?i)a(?-i:b)", "(?is)a.(?-s:b)", "(?i)a(?s-i).b"] {
+            let original = regex::Regex::new(regex).unwrap();
+            let (p, re) = explain_and_compile(regex).unwrap();
+            for input in &inputs {
+                assert_eq!(original.is_match(input), re.is_match(input), "{} on {:?}: {}", regex, input, p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_explain_errors() {
+        assert!(matches!(explain("a("), Err(ExplainError::Parse(..))));
+        assert_eq!(
+            Err(ExplainError::Unsupported(r"\1".to_owned())),
+            explain(r"(a)[\1]")
+        );
+        assert_eq!(
+            r"unsupported construct: \1",
+            explain(r"(a)[\1]").unwrap_err().to_string()
+        );
+        assert!(explain("a(").unwrap_err().to_string().starts_with("parse error"));
+    }
+
+    #[test]
+    fn test_explain_lossless() {
+        assert_explain("start_with(digit().captured()).and_then(backref(1))", r"(\d)\1");
+        for regex in &[r"(\d)\1", r"(a)(b)\2\1", r"a\p{Greek}b", r"(\w)\1+", r"[\\1]"] {
+            assert_eq!(*regex, explain(regex).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn test_explain_backref() {
+        assert_explain(
+            r#"start_with(word_character().one_or_more().named("word")).and_then(" ").and_then(backref_name("word"))"#,
+            r"(?P<word>\w+) \k<word>",
+        );
+        assert_explain(r#"start_with(letter().captured()).and_then(backref(12))"#, r"(\pL)\12");
+        assert!(matches!(explain(r"(a)\k<x"), Err(ExplainError::Parse(..))));
+        assert!(matches!(
+            explain_and_compile(r"(?P<q>['\x22])\w*\k<q>"),
+            Err(ExplainCompileError::Compile(..))
+        ));
     }
 
     fn assert_explain(expected: &str, regex: &str){
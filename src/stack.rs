@@ -0,0 +1,89 @@
+//! Build patterns step by step, with undo and redo
+
+use crate::builder::{Pattern, PatternList};
+
+/// A pattern built step by step, keeping each intermediate pattern so steps can be undone
+#[derive(Debug, Clone)]
+pub struct PatternStack {
+    /// Patterns after each step, the first one being the start
+    steps: Vec<Pattern>,
+    /// Undone patterns, the last one undone at the end
+    undone: Vec<Pattern>,
+}
+
+impl PatternStack {
+    /// Start with the given pattern
+    pub fn new<T: Into<Pattern>>(start: T) -> Self {
+        PatternStack {
+            steps: vec![start.into()],
+            undone: vec![],
+        }
+    }
+
+    /// Current pattern
+    pub fn current(&self) -> &Pattern {
+        self.steps.last().unwrap()
+    }
+
+    /// Append a new pattern, forgetting undone steps
+    pub fn and_then<T: Into<Pattern>>(&mut self, exp: T) -> &Pattern {
+        let p = self.current().clone().and_then(exp);
+        self.step(p)
+    }
+
+    /// Chain an alternative, forgetting undone steps
+    pub fn and_either<PL: PatternList>(&mut self, branches: PL) -> &Pattern {
+        let p = self.current().clone().and_either(branches);
+        self.step(p)
+    }
+
+    /// Undo the last step, returning the previous pattern, or None if there is no step to undo
+    pub fn undo(&mut self) -> Option<&Pattern> {
+        if self.steps.len() < 2 {
+            return None;
+        }
+        let p = self.steps.pop().unwrap();
+        self.undone.push(p);
+        Some(self.current())
+    }
+
+    /// Redo the last undone step, returning the pattern, or None if there is no step to redo
+    pub fn redo(&mut self) -> Option<&Pattern> {
+        let p = self.undone.pop()?;
+        self.steps.push(p);
+        Some(self.current())
+    }
+
+    /// Record a new step
+    fn step(&mut self, p: Pattern) -> &Pattern {
+        self.undone.clear();
+        self.steps.push(p);
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::*;
+
+    #[test]
+    fn test_undo_redo() {
+        let mut stack = PatternStack::new(digit().times(4));
+        stack.and_then("-");
+        stack.and_then(digit().times(2));
+        assert_eq!(r"\d{4}-\d{2}(a|b)", stack.and_either(("a", "b")).to_string());
+
+        assert_eq!(r"\d{4}-\d{2}", stack.undo().unwrap().to_string());
+        assert_eq!(r"\d{4}-", stack.undo().unwrap().to_string());
+        assert_eq!(r"\d{4}-\d{2}", stack.redo().unwrap().to_string());
+
+        assert_eq!(r"\d{4}-\d{2}x", stack.and_then("x").to_string());
+        assert!(stack.redo().is_none());
+        stack.undo();
+        stack.undo();
+        stack.undo();
+        assert_eq!(r"\d{4}", stack.current().to_string());
+        assert!(stack.undo().is_none());
+    }
+}
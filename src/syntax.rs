@@ -0,0 +1,500 @@
+//! Convert a pattern into regex_syntax structures
+
+use crate::builder::{Pattern, QuantifiedGroup, TEXT_META_CHARACTERS};
+use regex_syntax::ast::{
+    self, parse::Parser, Alternation, Assertion, AssertionKind, Ast, CaptureName, Class, ClassPerl,
+    ClassPerlKind, ClassUnicode, ClassUnicodeKind, Concat, Flag, Flags, FlagsItem, FlagsItemKind,
+    Group, GroupKind, HexLiteralKind, Literal, LiteralKind, Position, Repetition, RepetitionKind, RepetitionOp,
+    RepetitionRange, Span, SpecialLiteralKind, Visitor,
+};
+use regex_syntax::hir::{self, Hir, HirKind};
+
+impl Pattern {
+    /// Convert the pattern into the equivalent regex_syntax AST, the one `explain`
+    /// would get by parsing the rendered regular expression.
+    /// Spans are not meaningful since there is no source text.
+    /// None if the pattern has no regex_syntax equivalent: lookarounds, backreferences,
+    /// patterns that `any_except` can't negate and raw fragments that don't parse
+    pub fn to_ast(&self) -> Option<Ast> {
+        self.to_inner_ast(&mut 0)
+    }
+
+    /// Conversion to AST, numbering capture groups as we go
+    fn to_inner_ast(&self, index: &mut u32) -> Option<Ast> {
+        let ast = match self {
+            Pattern::Sequence(exps) => Ast::Concat(Concat {
+                span: span(),
+                asts: exps
+                    .iter()
+                    .map(|e| match e {
+                        Pattern::Or(..) if exps.len() > 1 && !e.is_class_union() => {
+                            capture(e, index)
+                        }
+                        _ => e.to_inner_ast(index),
+                    })
+                    .collect::<Option<_>>()?,
+            }),
+            Pattern::Text(t) => {
+                let mut asts: Vec<Ast> = t
+                    .chars()
+                    .map(|c| {
+                        if TEXT_META_CHARACTERS.contains(c) {
+                            literal(c, LiteralKind::Punctuation)
+                        } else {
+                            literal(c, LiteralKind::Verbatim)
+                        }
+                    })
+                    .collect();
+                if asts.len() == 1 {
+                    asts.pop()?
+                } else {
+                    Ast::Concat(Concat { span: span(), asts })
+                }
+            }
+            Pattern::Raw(t) => parse(t)?,
+            Pattern::Or(..) if self.is_class_union() => parse(&self.to_string())?,
+            Pattern::Or(exps) => Ast::Alternation(Alternation {
+                span: span(),
+                asts: exps
+                    .iter()
+                    .map(|e| e.to_inner_ast(index))
+                    .collect::<Option<_>>()?,
+            }),
+            Pattern::Many { exp, low, .. } if exp.is_anchor() => {
+                if *low > 0 {
+                    exp.to_inner_ast(index)?
+                } else {
+                    Ast::Empty(span())
+                }
+            }
+            Pattern::Many {
+                exp,
+                low,
+                high,
+                lazy,
+            } => {
+                let ast = match exp.quantified_group() {
+                    QuantifiedGroup::Bare => exp.to_inner_ast(index)?,
+                    QuantifiedGroup::NonCapturing => non_capturing(exp.to_inner_ast(index)?),
+                    QuantifiedGroup::Capturing => capture(exp, index)?,
+                };
+                let kind = match (*low, *high) {
+                    (0, 1) => RepetitionKind::ZeroOrOne,
+                    (0, 0) => RepetitionKind::ZeroOrMore,
+                    (1, 0) => RepetitionKind::OneOrMore,
+                    (l, h) if l == h => RepetitionKind::Range(RepetitionRange::Exactly(l)),
+                    (l, 0) => RepetitionKind::Range(RepetitionRange::AtLeast(l)),
+                    (l, h) => RepetitionKind::Range(RepetitionRange::Bounded(l, h)),
+                };
+                Ast::Repetition(Repetition {
+                    span: span(),
+                    op: RepetitionOp { span: span(), kind },
+                    greedy: !lazy,
+                    ast: Box::new(ast),
+                })
+            }
+            Pattern::Digit => perl(ClassPerlKind::Digit, false),
+            Pattern::Letter => unicode('L', false),
+            Pattern::Number => unicode('N', false),
+            Pattern::WordCharacter => perl(ClassPerlKind::Word, false),
+            Pattern::Whitespace => perl(ClassPerlKind::Space, false),
+            Pattern::InputStart => assertion(AssertionKind::StartLine),
+            Pattern::InputEnd => assertion(AssertionKind::EndLine),
+            Pattern::TextStart => assertion(AssertionKind::StartText),
+            Pattern::TextEnd => assertion(AssertionKind::EndText),
+            Pattern::WordBoundary { negated: false } => assertion(AssertionKind::WordBoundary),
+            Pattern::WordBoundary { negated: true } => assertion(AssertionKind::NotWordBoundary),
+            Pattern::Not(exp) => match **exp {
+                Pattern::PosixClass(..) => parse(&self.to_string())?,
+                _ if exp.class_items().is_some() => parse(&self.to_string())?,
+                Pattern::Digit => perl(ClassPerlKind::Digit, true),
+                Pattern::Letter => unicode('L', true),
+                Pattern::Number => unicode('N', true),
+                Pattern::WordCharacter => perl(ClassPerlKind::Word, true),
+                Pattern::Whitespace => perl(ClassPerlKind::Space, true),
+                _ => return None,
+            },
+            Pattern::Any => Ast::Dot(span()),
+            Pattern::Named { exp, name } => {
+                *index += 1;
+                let kind = GroupKind::CaptureName(CaptureName {
+                    span: span(),
+                    name: name.clone(),
+                    index: *index,
+                });
+                group(kind, exp.to_inner_ast(index)?)
+            }
+            Pattern::Control('\t') => literal('\t', LiteralKind::Special(SpecialLiteralKind::Tab)),
+            Pattern::Control('\n') => {
+                literal('\n', LiteralKind::Special(SpecialLiteralKind::LineFeed))
+            }
+            Pattern::Control('\r') => {
+                literal('\r', LiteralKind::Special(SpecialLiteralKind::CarriageReturn))
+            }
+            Pattern::Control(c) => literal(*c, LiteralKind::HexFixed(HexLiteralKind::X)),
+            Pattern::HexChar(code) => {
+                let c = std::char::from_u32(*code)?;
+                if *code <= 0xFF {
+                    literal(c, LiteralKind::HexFixed(HexLiteralKind::X))
+                } else {
+                    literal(c, LiteralKind::HexBrace(HexLiteralKind::X))
+                }
+            }
+            Pattern::Curated { exp, .. } => exp.to_inner_ast(index)?,
+            Pattern::Group {
+                exp,
+                capturing: true,
+            } => capture(exp, index)?,
+            Pattern::PosixClass(..)
+            | Pattern::CharRange { .. }
+            | Pattern::CharSet { .. }
+            | Pattern::UnicodeClass { .. } => parse(&self.to_string())?,
+            // regex_syntax has no lookaround or backreference node
+            Pattern::LookAhead { .. } | Pattern::LookBehind { .. } | Pattern::BackRef(..) => {
+                return None
+            }
+            Pattern::Group { exp, .. } => non_capturing(exp.to_inner_ast(index)?),
+            Pattern::Flags { flags, exp } => {
+                let kind = GroupKind::NonCapturing(Flags {
+                    span: span(),
+                    items: flags
+                        .chars()
+                        .filter_map(|c| match c {
+                            '-' => Some(FlagsItemKind::Negation),
+                            c => flag(c).map(FlagsItemKind::Flag),
+                        })
+                        .map(|kind| FlagsItem { span: span(), kind })
+                        .collect(),
+                });
+                group(kind, exp.to_inner_ast(index)?)
+            }
+        };
+        Some(ast)
+    }
+
+    /// Number of capture groups in the rendered regular expression,
+    /// including the groups added implicitly when rendering.
+    /// Counted on the pattern, so lookarounds and backreferences are supported
+    pub fn count_capture_groups(&self) -> usize {
+        match self {
+            Pattern::Sequence(exps) => exps
+                .iter()
+                .map(|e| match e {
+                    Pattern::Or(..) if exps.len() > 1 && !e.is_class_union() => {
+                        1 + e.count_capture_groups()
+                    }
+                    _ => e.count_capture_groups(),
+                })
+                .sum(),
+            Pattern::Raw(r) => match parse(r) {
+                Some(ast) => ast::visit(&ast, CaptureCounter(0)).unwrap_or(0),
+                None => count_raw_groups(r),
+            },
+            // anchors are rendered once or not at all
+            Pattern::Many { exp, low, .. } if exp.is_anchor() => {
+                if *low > 0 {
+                    exp.count_capture_groups()
+                } else {
+                    0
+                }
+            }
+            Pattern::Many { exp, .. } if exp.quantified_group() == QuantifiedGroup::Capturing => {
+                1 + exp.count_capture_groups()
+            }
+            Pattern::Named { exp, .. }
+            | Pattern::Group {
+                exp,
+                capturing: true,
+            } => 1 + exp.count_capture_groups(),
+            p => p.children().into_iter().map(Pattern::count_capture_groups).sum(),
+        }
+    }
+}
+
+impl Pattern {
+    /// Is every text matched by this pattern also matched by the other one?
+    /// Only decided for single characters and character classes, `None` otherwise
+    pub fn is_subset_of(&self, other: &Pattern) -> Option<bool> {
+        let mut class = self.to_class()?;
+        class.difference(&other.to_class()?);
+        Some(class.ranges().is_empty())
+    }
+
+    /// The set of characters matched by the pattern, if it matches a single character
+    fn to_class(&self) -> Option<hir::ClassUnicode> {
+        let hir = regex_syntax::Parser::new().parse(&self.to_string()).ok()?;
+        class_of(&hir)
+    }
+}
+
+/// The set of characters matched by a HIR, if it matches a single character
+fn class_of(hir: &Hir) -> Option<hir::ClassUnicode> {
+    match hir.kind() {
+        HirKind::Class(hir::Class::Unicode(c)) => Some(c.clone()),
+        HirKind::Literal(hir::Literal::Unicode(c)) => {
+            Some(hir::ClassUnicode::new(vec![hir::ClassUnicodeRange::new(*c, *c)]))
+        }
+        HirKind::Group(g) => class_of(&g.hir),
+        HirKind::Alternation(hirs) => hirs.iter().try_fold(
+            hir::ClassUnicode::empty(),
+            |mut acc, h| {
+                acc.union(&class_of(h)?);
+                Some(acc)
+            },
+        ),
+        _ => None,
+    }
+}
+
+/// Count capture groups in a raw fragment `regex_syntax` can't parse, like a lookaround:
+/// opening parentheses outside of classes, unless they start a non capturing group,
+/// a lookaround or a flag group
+fn count_raw_groups(raw: &str) -> usize {
+    let chars: Vec<char> = raw.chars().collect();
+    let (mut i, mut class_depth, mut count) = (0, 0, 0);
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' => class_depth += 1,
+            ']' if class_depth > 0 => class_depth -= 1,
+            '(' if class_depth == 0 => {
+                let rest: String = chars[i + 1..].iter().take(3).collect();
+                let capturing = match rest.strip_prefix('?') {
+                    None => true,
+                    Some(r) => {
+                        r.starts_with("P<")
+                            || (r.starts_with('<') && !r.starts_with("<=") && !r.starts_with("<!"))
+                    }
+                };
+                if capturing {
+                    count += 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    count
+}
+
+/// Count capture groups in an AST
+struct CaptureCounter(usize);
+
+impl Visitor for CaptureCounter {
+    type Output = usize;
+    type Err = ();
+
+    fn finish(self) -> Result<usize, ()> {
+        Ok(self.0)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), ()> {
+        if let Ast::Group(g) = ast {
+            if g.is_capturing() {
+                self.0 += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Span used for all generated nodes
+fn span() -> Span {
+    let p = Position::new(0, 1, 1);
+    Span::new(p, p)
+}
+
+/// Parse an already formed regular expression, None if it does not parse
+fn parse(regex: &str) -> Option<Ast> {
+    Parser::new().parse(regex).ok()
+}
+
+/// Capturing group around the given pattern
+fn capture(exp: &Pattern, index: &mut u32) -> Option<Ast> {
+    *index += 1;
+    let kind = GroupKind::CaptureIndex(*index);
+    Some(group(kind, exp.to_inner_ast(index)?))
+}
+
+/// Non capturing group around the given AST
+fn non_capturing(ast: Ast) -> Ast {
+    let kind = GroupKind::NonCapturing(Flags {
+        span: span(),
+        items: vec![],
+    });
+    group(kind, ast)
+}
+
+/// Group of the given kind
+fn group(kind: GroupKind, ast: Ast) -> Ast {
+    Ast::Group(Group {
+        span: span(),
+        kind,
+        ast: Box::new(ast),
+    })
+}
+
+/// Flag from its letter
+fn flag(c: char) -> Option<Flag> {
+    match c {
+        'i' => Some(Flag::CaseInsensitive),
+        'm' => Some(Flag::MultiLine),
+        's' => Some(Flag::DotMatchesNewLine),
+        'U' => Some(Flag::SwapGreed),
+        'u' => Some(Flag::Unicode),
+        'x' => Some(Flag::IgnoreWhitespace),
+        _ => None,
+    }
+}
+
+/// Single character literal
+fn literal(c: char, kind: LiteralKind) -> Ast {
+    Ast::Literal(Literal {
+        span: span(),
+        kind,
+        c,
+    })
+}
+
+/// Perl character class
+fn perl(kind: ClassPerlKind, negated: bool) -> Ast {
+    Ast::Class(Class::Perl(ClassPerl {
+        span: span(),
+        kind,
+        negated,
+    }))
+}
+
+/// One letter unicode class
+fn unicode(c: char, negated: bool) -> Ast {
+    Ast::Class(Class::Unicode(ClassUnicode {
+        span: span(),
+        negated,
+        kind: ClassUnicodeKind::OneLetter(c),
+    }))
+}
+
+/// Assertion
+fn assertion(kind: AssertionKind) -> Ast {
+    Ast::Assertion(Assertion { span: span(), kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::*;
+    use crate::curated::{key_value_list, text_ci, word};
+    use regex_syntax::ast::{self, parse::Parser};
+
+    #[test]
+    fn test_to_ast() {
+        assert_to_ast(text("Handel"));
+        assert_to_ast(text("a.b+c").and_then(text(r"(\)")));
+        assert_to_ast(either(("gray", "grey")));
+        assert_to_ast(start_with("gr").and_either(("a", "e")).and_then("y"));
+        assert_to_ast(start_with("colo").and_maybe("u").and_then("r"));
+        assert_to_ast(digit().many(2, 3));
+        assert_to_ast(digit().repeat(Repeat::AtLeast(3)));
+        assert_to_ast(text("ab").times(2));
+        assert_to_ast(
+            at_start()
+                .and_then(digit().times(4))
+                .and_then("-")
+                .and_then(digit().times(2))
+                .must_end(),
+        );
+        assert_to_ast(
+            any_except(digit())
+                .and_then(any_except(letter()))
+                .and_then(any_except(word_character())),
+        );
+        assert_to_ast(
+            start_with(digit().times(4).named("y"))
+                .and_then("-")
+                .and_then(digit().times(2).named("m")),
+        );
+        assert_to_ast(start_with(anything()).and_then(control_char('A')));
+        assert_to_ast(unicode_category("Greek").and_then(any_except(unicode_category("Lu"))));
+        assert_to_ast(start_with(hex_char(0x41)).and_then(hex_char(0x1F600)).and_then(tab()));
+        assert_to_ast(digit().times(4).captured().and_then("-"));
+        assert_to_ast(start_with("id=").and_then(digit().and_then("x").case_insensitive()));
+        assert_to_ast(at_start().times(3).and_then("a").and_then(maybe(at_start())));
+        assert_to_ast(char_range('a', 'z').many(1, 0).and_then(char_range('-', ']')));
+        assert_to_ast(either(("a", "b")).many(2, 4));
+        assert_to_ast(whitespace().many(1, 0).and_then(any_except(whitespace())));
+        assert_to_ast(any_except("a]").and_then(any_except(char_range('a', 'z'))));
+        assert_to_ast(one_of(['a', ']']).and_then(any_except(one_of(['-', '^']))));
+        assert_to_ast(posix_class("alpha").and_then(any_except(posix_class("digit"))));
+        assert_to_ast(word_boundary().and_then("a").and_then(not_word_boundary()));
+        assert_to_ast(at_text_start().and_then("a").must_end_text());
+        assert_to_ast(start_with("id=").and_then(text_ci("ab").one_or_more()));
+        assert_to_ast(key_value_list(word(), digit().one_or_more(), '='));
+    }
+
+    #[test]
+    fn test_to_ast_unsupported() {
+        assert!(start_with("a").and_then(followed_by("b")).to_ast().is_none());
+        assert!(start_with(not_preceded_by("a")).and_then("b").to_ast().is_none());
+        assert!(digit().captured().and_then(backref(1)).to_ast().is_none());
+        assert!(maybe(backref_name("l")).to_ast().is_none());
+        assert!(any_except(digit().one_or_more()).one_or_more().to_ast().is_none());
+        assert!(start_with("a").and_then(raw("(b")).to_ast().is_none());
+        assert!(either(("a", raw("b)"))).to_ast().is_none());
+    }
+
+    #[test]
+    fn test_count_capture_groups() {
+        assert_eq!(0, digit().times(4).count_capture_groups());
+        assert_eq!(1, digit().times(4).captured().count_capture_groups());
+        assert_eq!(
+            1,
+            start_with("gr").and_either(("a", "e")).and_then("y").count_capture_groups()
+        );
+        assert_eq!(
+            3,
+            start_with(digit().times(4).named("y"))
+                .and_then("-")
+                .and_then(digit().times(2).named("m"))
+                .and_then("-")
+                .and_then(digit().times(2).named("d"))
+                .count_capture_groups()
+        );
+        assert_eq!(1, digit().captured().and_then(followed_by("a")).count_capture_groups());
+        assert_eq!(1, digit().captured().and_then(backref(1)).count_capture_groups());
+        assert_eq!(
+            2,
+            start_with(letter().named("l"))
+                .and_then(preceded_by(digit().captured()))
+                .and_then(backref_name("l"))
+                .count_capture_groups()
+        );
+        assert_eq!(2, text("ab").many(1, 0).many(0, 0).count_capture_groups());
+        let p = at_start().times(2).and_then(either(("a", "b")).many(1, 0));
+        assert_eq!(0, p.count_capture_groups());
+        assert_eq!(1, raw(r"(?<=\$)(\d+)(?:x)").count_capture_groups());
+        assert_eq!(1, start_with("a").and_either(("b", "c")).count_capture_groups());
+    }
+
+    #[test]
+    fn test_is_subset_of() {
+        let a_c = raw("[a-c]");
+        let a_z = raw("[a-z]");
+        assert_eq!(Some(true), a_c.is_subset_of(&a_z));
+        assert_eq!(Some(false), a_z.is_subset_of(&a_c));
+        assert_eq!(Some(true), digit().is_subset_of(&word_character()));
+        assert_eq!(Some(false), word_character().is_subset_of(&digit()));
+        assert_eq!(Some(true), text("b").is_subset_of(&a_c));
+        assert_eq!(Some(false), text("d").is_subset_of(&a_c));
+        assert_eq!(Some(true), either(("a", "b")).is_subset_of(&a_c));
+        assert_eq!(None, text("ab").is_subset_of(&a_z));
+        assert_eq!(None, digit().many(1, 0).is_subset_of(&word_character()));
+    }
+
+    fn assert_to_ast(p: Pattern) {
+        let printed = p.to_ast().unwrap().to_string();
+        assert_eq!(p.to_string(), printed);
+        let reparsed = Parser::new().parse(&printed).unwrap();
+        assert_eq!(p.to_string(), reparsed.to_string());
+        let groups = ast::visit(&reparsed, super::CaptureCounter(0)).unwrap();
+        assert_eq!(groups, p.count_capture_groups());
+    }
+}
@@ -0,0 +1,217 @@
+//! Convert patterns to and from a flat list of tokens
+
+use crate::builder::Pattern;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result};
+use std::iter::Peekable;
+
+/// A token of the flat representation of a pattern
+#[derive(Debug, Clone)]
+pub enum Token {
+    /// A pattern without sub-patterns
+    Leaf(Pattern),
+    /// Start of a pattern with sub-patterns, ended by the matching `Close`
+    Open(Marker),
+    /// End of the last opened pattern
+    Close,
+}
+
+/// Kind of a pattern with sub-patterns
+#[derive(Debug, Clone, PartialEq)]
+pub enum Marker {
+    /// Sequence of patterns
+    Sequence,
+    /// Alternative
+    Or,
+    /// Repetition
+    Many { low: u32, high: u32, lazy: bool },
+    /// Negation
+    Not,
+    /// Named group
+    Named(String),
+    /// Group, capturing or not
+    Group { capturing: bool },
+    /// Curated helper, with its code
+    Curated(String),
+    /// Lookahead assertion
+    LookAhead { negated: bool },
+    /// Lookbehind assertion
+    LookBehind { negated: bool },
+    /// Inline flags
+    Flags(String),
+}
+
+/// Error rebuilding a pattern from tokens
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenError {
+    /// The tokens ended before all patterns were closed
+    UnexpectedEnd,
+    /// A close token without matching open token
+    UnexpectedClose,
+    /// A pattern that needs exactly one sub-pattern got a different number
+    ChildCount(Marker, usize),
+    /// Tokens left after the pattern was closed
+    TrailingTokens,
+}
+
+impl Display for TokenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            TokenError::UnexpectedEnd => write!(f, "unexpected end of tokens"),
+            TokenError::UnexpectedClose => write!(f, "close token without open token"),
+            TokenError::ChildCount(m, n) => {
+                write!(f, "{:?} needs exactly one sub-pattern, got {}", m, n)
+            }
+            TokenError::TrailingTokens => write!(f, "tokens left after the pattern"),
+        }
+    }
+}
+
+impl Error for TokenError {}
+
+impl Pattern {
+    /// Flatten the pattern into tokens, sub-patterns between open and close tokens
+    pub fn to_tokens(&self) -> Vec<Token> {
+        let mut tokens = vec![];
+        self.push_tokens(&mut tokens);
+        tokens
+    }
+
+    /// Rebuild a pattern from tokens produced by `to_tokens`
+    pub fn from_tokens<I: IntoIterator<Item = Token>>(
+        tokens: I,
+    ) -> std::result::Result<Pattern, TokenError> {
+        let mut it = tokens.into_iter().peekable();
+        let p = from_inner_tokens(&mut it)?;
+        match it.next() {
+            None => Ok(p),
+            Some(_) => Err(TokenError::TrailingTokens),
+        }
+    }
+
+    /// Accumulate tokens
+    fn push_tokens(&self, tokens: &mut Vec<Token>) {
+        let marker = match self {
+            Pattern::Sequence(..) => Marker::Sequence,
+            Pattern::Or(..) => Marker::Or,
+            Pattern::Many {
+                low, high, lazy, ..
+            } => Marker::Many {
+                low: *low,
+                high: *high,
+                lazy: *lazy,
+            },
+            Pattern::Not(..) => Marker::Not,
+            Pattern::Named { name, .. } => Marker::Named(name.clone()),
+            Pattern::Group { capturing, .. } => Marker::Group {
+                capturing: *capturing,
+            },
+            Pattern::Curated { code, .. } => Marker::Curated(code.clone()),
+            Pattern::LookAhead { negated, .. } => Marker::LookAhead { negated: *negated },
+            Pattern::LookBehind { negated, .. } => Marker::LookBehind { negated: *negated },
+            Pattern::Flags { flags, .. } => Marker::Flags(flags.clone()),
+            _ => {
+                tokens.push(Token::Leaf(self.clone()));
+                return;
+            }
+        };
+        tokens.push(Token::Open(marker));
+        for child in self.children() {
+            child.push_tokens(tokens);
+        }
+        tokens.push(Token::Close);
+    }
+}
+
+/// Rebuild the next pattern
+fn from_inner_tokens<I: Iterator<Item = Token>>(
+    it: &mut Peekable<I>,
+) -> std::result::Result<Pattern, TokenError> {
+    match it.next() {
+        None => Err(TokenError::UnexpectedEnd),
+        Some(Token::Close) => Err(TokenError::UnexpectedClose),
+        Some(Token::Leaf(p)) => Ok(p),
+        Some(Token::Open(marker)) => {
+            let mut children = vec![];
+            loop {
+                match it.peek() {
+                    None => return Err(TokenError::UnexpectedEnd),
+                    Some(Token::Close) => {
+                        it.next();
+                        break;
+                    }
+                    Some(_) => children.push(from_inner_tokens(it)?),
+                }
+            }
+            match marker {
+                Marker::Sequence => Ok(Pattern::Sequence(children)),
+                Marker::Or => Ok(Pattern::Or(children)),
+                m if children.len() != 1 => Err(TokenError::ChildCount(m, children.len())),
+                m => {
+                    let exp = Box::new(children.pop().unwrap());
+                    Ok(match m {
+                        Marker::Many { low, high, lazy } => Pattern::Many {
+                            exp,
+                            low,
+                            high,
+                            lazy,
+                        },
+                        Marker::Not => Pattern::Not(exp),
+                        Marker::Named(name) => Pattern::Named { exp, name },
+                        Marker::Group { capturing } => Pattern::Group { exp, capturing },
+                        Marker::Curated(code) => Pattern::Curated { exp, code },
+                        Marker::LookAhead { negated } => Pattern::LookAhead { exp, negated },
+                        Marker::LookBehind { negated } => Pattern::LookBehind { exp, negated },
+                        Marker::Flags(flags) => Pattern::Flags { flags, exp },
+                        Marker::Sequence | Marker::Or => unreachable!(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::*;
+
+    #[test]
+    fn test_tokens_round_trip() {
+        let date = at_start()
+            .and_then(digit().times(4).named("y"))
+            .and_then("-")
+            .and_then(digit().times(2).named("m"))
+            .and_then("-")
+            .and_then(digit().times(2).named("d"))
+            .must_end();
+        let tokens = date.to_tokens();
+        assert_eq!(21, tokens.len());
+        assert!(matches!(tokens[0], Token::Open(Marker::Sequence)));
+        assert!(matches!(tokens[1], Token::Leaf(Pattern::InputStart)));
+        assert!(matches!(&tokens[2], Token::Open(Marker::Named(n)) if n == "y"));
+        let rebuilt = Pattern::from_tokens(tokens).unwrap();
+        assert_eq!(date.to_string(), rebuilt.to_string());
+        assert_eq!(date.to_code(), rebuilt.to_code());
+    }
+
+    #[test]
+    fn test_tokens_errors() {
+        assert_eq!(
+            Some(TokenError::UnexpectedEnd),
+            Pattern::from_tokens(vec![Token::Open(Marker::Sequence)]).err()
+        );
+        assert_eq!(
+            Some(TokenError::UnexpectedClose),
+            Pattern::from_tokens(vec![Token::Close]).err()
+        );
+        assert_eq!(
+            Some(TokenError::ChildCount(Marker::Not, 0)),
+            Pattern::from_tokens(vec![Token::Open(Marker::Not), Token::Close]).err()
+        );
+        assert_eq!(
+            Some(TokenError::TrailingTokens),
+            Pattern::from_tokens(vec![Token::Leaf(digit()), Token::Leaf(digit())]).err()
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Detect likely mistakes in patterns
+
+use crate::builder::Pattern;
+
+/// A likely mistake in a pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The same anchor is repeated consecutively, like `^^`
+    RedundantAnchor(String),
+    /// A lookaround is quantified, like `(?=a)+`: repeating a zero-width assertion is useless
+    QuantifiedLookaround(String),
+    /// An anchor is quantified, like `^{3}`: the quantifier is dropped when rendering
+    QuantifiedAnchor(String),
+    /// A pattern that can't be negated is used with `any_except`, and renders as nothing
+    UnsupportedNegation(String),
+}
+
+impl Pattern {
+    /// Check the pattern and all its sub-patterns for likely mistakes
+    pub fn validate(&self) -> Vec<Warning> {
+        let mut warnings = vec![];
+        self.validate_into(&mut warnings);
+        warnings
+    }
+
+    /// Accumulate warnings
+    fn validate_into(&self, warnings: &mut Vec<Warning>) {
+        if let Pattern::Sequence(exps) = self {
+            for pair in exps.windows(2) {
+                if pair[0].is_anchor() && pair[0].to_string() == pair[1].to_string() {
+                    warnings.push(Warning::RedundantAnchor(pair[0].to_string()));
+                }
+            }
+        }
+        if let Pattern::Many { exp, .. } = self {
+            if let Pattern::LookAhead { .. } | Pattern::LookBehind { .. } = **exp {
+                warnings.push(Warning::QuantifiedLookaround(self.to_string()));
+            }
+            if exp.is_anchor() {
+                warnings.push(Warning::QuantifiedAnchor(exp.to_string()));
+            }
+        }
+        if let Pattern::Not(exp) = self {
+            if !exp.can_negate() {
+                warnings.push(Warning::UnsupportedNegation(exp.to_string()));
+            }
+        }
+        for child in self.children() {
+            child.validate_into(warnings);
+        }
+    }
+
+    /// Does the pattern repeat a sub-pattern which itself repeats a variable number of times,
+    /// like `(a+)+`? Backtracking engines can take exponential time on such patterns when
+    /// the match fails. The check is structural, so it does not look at whether the inputs
+    /// of both repetitions actually overlap. The `regex` crate is not affected, but the
+    /// rendered regex may be used with other engines
+    pub fn has_nested_quantifier_risk(&self) -> bool {
+        match self {
+            Pattern::Many { exp, high, .. } if *high != 1 && exp.has_variable_repetition() => {
+                true
+            }
+            _ => self.children().iter().any(|c| c.has_nested_quantifier_risk()),
+        }
+    }
+
+    /// Does the pattern contain a repetition with a variable count?
+    fn has_variable_repetition(&self) -> bool {
+        match self {
+            Pattern::Many { low, high, .. } if *high == 0 || low != high => true,
+            _ => self.children().iter().any(|c| c.has_variable_repetition()),
+        }
+    }
+
+    /// Is the pattern a zero-width anchor?
+    pub(crate) fn is_anchor(&self) -> bool {
+        matches!(
+            self,
+            Pattern::InputStart
+                | Pattern::InputEnd
+                | Pattern::TextStart
+                | Pattern::TextEnd
+                | Pattern::WordBoundary { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::*;
+
+    #[test]
+    fn test_redundant_anchor() {
+        let p = at_start().and_then(at_start()).and_then("a").must_end().must_end();
+        assert_eq!(
+            vec![
+                Warning::RedundantAnchor("^".to_owned()),
+                Warning::RedundantAnchor("$".to_owned())
+            ],
+            p.validate()
+        );
+        assert_eq!(
+            vec![Warning::RedundantAnchor("^".to_owned())],
+            start_with("a")
+                .and_either((at_start().and_then(at_start()), "b"))
+                .validate()
+        );
+        assert!(at_start().and_then("a").must_end().validate().is_empty());
+    }
+
+    #[test]
+    fn test_nested_quantifier_risk() {
+        let risky = text("a").many(1, 0).captured().many(1, 0);
+        assert_eq!("(a+)+", risky.to_string());
+        assert!(risky.has_nested_quantifier_risk());
+        assert!(text("a").many(0, 0).captured().many(0, 0).has_nested_quantifier_risk());
+        assert!(start_with("x")
+            .and_then(start_with("a").and_many("b").many(2, 0))
+            .has_nested_quantifier_risk());
+
+        assert!(!text("a").many(1, 0).and_many("b").has_nested_quantifier_risk());
+        assert!(!text("ab").many(1, 0).has_nested_quantifier_risk());
+        assert!(!digit().times(2).captured().many(1, 0).has_nested_quantifier_risk());
+        assert!(!text("a").many(1, 0).captured().has_nested_quantifier_risk());
+    }
+
+    #[test]
+    fn test_unsupported_negation() {
+        assert_eq!(
+            vec![Warning::UnsupportedNegation(r"\d+".to_owned())],
+            any_except(digit().many(1, 0)).validate()
+        );
+        assert!(any_except("a").and_then(any_except(digit())).validate().is_empty());
+    }
+
+    #[test]
+    fn test_quantified_anchor() {
+        let p = at_start().times(3).and_then("a");
+        assert_eq!("^a", p.to_string());
+        assert_eq!(vec![Warning::QuantifiedAnchor("^".to_owned())], p.validate());
+        let p = start_with("a").and_then(maybe(Pattern::InputEnd));
+        assert_eq!("a", p.to_string());
+        assert_eq!(vec![Warning::QuantifiedAnchor("$".to_owned())], p.validate());
+    }
+
+    #[test]
+    fn test_quantified_lookaround() {
+        let p = start_with("a").and_then(followed_by("b").times(2));
+        assert_eq!("a(?=b){2}", p.to_string());
+        assert_eq!(
+            vec![Warning::QuantifiedLookaround("(?=b){2}".to_owned())],
+            p.validate()
+        );
+        assert_eq!(
+            vec![Warning::QuantifiedLookaround("(?!b)+".to_owned())],
+            not_followed_by("b").many(1, 0).validate()
+        );
+        assert!(start_with("a").and_then(followed_by("b")).validate().is_empty());
+    }
+}